@@ -0,0 +1,231 @@
+//! Module for the accumulation of Bits cheered by Users in each Channel. The
+//!     totals cover the current session, unless they are set to be kept in a
+//!     file, in which case they carry over into later sessions.
+
+use std::{collections::HashMap, io};
+
+use hexchat::get_pref_int;
+use parking_lot::RwLock;
+
+use super::storage;
+
+
+/// Name of the file, in the Plugin directory, which keeps the Bits cheered.
+pub const BITS_FILE: &str = "bits.log";
+/// Default number of Bits from which a Cheer is alerted as a big Cheer.
+pub const BIG_DEFAULT: usize = 1_000;
+/// Most lines kept in the Bits file. Past this, the file is rewritten with one
+///     line for each User in each Channel, keeping only the highest totals.
+pub const LOG_LIMIT: usize = 5_000;
+
+
+/// Bits: A mapping of Channel names to the total Bits cheered by each User.
+#[derive(Default)]
+pub struct Bits {
+    inner: HashMap<String, HashMap<String, usize>>,
+    /// Whether the Bits file has been added in, which is only done once.
+    loaded: bool,
+}
+
+impl Bits {
+    /// Add a Cheer to the total of a User in a Channel. Returns the new total.
+    ///
+    /// Input: `&str`, `&str`, `usize`
+    /// Return: `usize`
+    pub fn add(&mut self, channel: &str, user: &str, amount: usize) -> usize {
+        let total = self.inner.entry(channel.to_ascii_lowercase()).or_default()
+            .entry(user.to_ascii_lowercase()).or_default();
+
+        *total += amount;
+        *total
+    }
+
+    /// Get the Users who have cheered the most Bits in a Channel, highest
+    ///     first. Users with the same total are sorted by name.
+    ///
+    /// Input: `&str`, `usize`
+    /// Return: `Vec<(&str, usize)>`
+    pub fn leaderboard(&self, channel: &str, count: usize) -> Vec<(&str, usize)> {
+        let mut board: Vec<(&str, usize)> = match self.inner.get(&channel.to_ascii_lowercase()) {
+            Some(users) => users.iter().map(|(u, &n)| (u.as_str(), n)).collect(),
+            None => return Vec::new(),
+        };
+
+        board.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        board.truncate(count);
+        board
+    }
+
+    /// Add Cheers from lines of the form `channel user amount`, as kept in the
+    ///     Bits file. Returns the number of lines used.
+    ///
+    /// Input: `&[String]`
+    /// Return: `usize`
+    pub fn load(&mut self, lines: &[String]) -> usize {
+        let mut used = 0;
+
+        for line in lines {
+            let mut parts = line.split_whitespace();
+
+            if let (Some(channel), Some(user), Some(Ok(amount))) = (
+                parts.next(),
+                parts.next(),
+                parts.next().map(str::parse),
+            ) {
+                self.add(channel, user, amount);
+                used += 1;
+            }
+        }
+
+        used
+    }
+
+    /// Write the totals as lines of the form `channel user amount`, highest
+    ///     first, and at most a number of them.
+    ///
+    /// Input: `usize`
+    /// Return: `Vec<String>`
+    pub fn lines(&self, limit: usize) -> Vec<String> {
+        let mut all: Vec<(&str, &str, usize)> = self.inner.iter()
+            .flat_map(|(ch, users)| users.iter().map(move |(u, &n)| (ch.as_str(), u.as_str(), n)))
+            .collect();
+
+        all.sort_unstable_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(b.0)).then(a.1.cmp(b.1)));
+        all.into_iter()
+            .take(limit)
+            .map(|(ch, user, n)| format!("{} {} {}", ch, user, n))
+            .collect()
+    }
+
+    /// Add the Cheers kept in the Bits file, unless they have already been
+    ///     added. A file which has grown too long is trimmed.
+    ///
+    /// Return: `io::Result<()>`
+    fn load_file(&mut self) -> io::Result<()> {
+        if self.loaded { return Ok(()); }
+
+        let lines = storage::read_lines(BITS_FILE)?;
+        self.load(&lines);
+        self.loaded = true;
+
+        if lines.len() > LOG_LIMIT {
+            let mut kept = Self::default();
+            kept.load(&lines);
+            storage::write_lines(BITS_FILE, &kept.lines(LOG_LIMIT))?;
+        }
+
+        Ok(())
+    }
+}
+
+
+safe_static! {
+    pub static lazy BITS: RwLock<Bits> = {
+        let mut bits = Bits::default();
+
+        if get_pref_int("PREF_bits_persist").unwrap_or(0) != 0 {
+            bits.load_file().ok();
+        }
+
+        RwLock::new(bits)
+    };
+}
+
+
+/// Add the Cheers kept in the Bits file to the totals, when they are set to be
+///     kept during a session. This is done at most once per session.
+///
+/// Return: `io::Result<()>`
+pub fn restore() -> io::Result<()> { BITS.write().load_file() }
+
+
+/// Write a Cheer into the Bits file, if the totals are set to be kept.
+///
+/// Input: `&str`, `&str`, `usize`
+/// Return: `io::Result<()>`
+pub fn keep(channel: &str, user: &str, amount: usize) -> io::Result<()> {
+    if get_pref_int("PREF_bits_persist").unwrap_or(0) != 0 {
+        storage::append_line(BITS_FILE, &format!(
+            "{} {} {}",
+            channel.to_ascii_lowercase(),
+            user.to_ascii_lowercase(),
+            amount,
+        ))?;
+    }
+
+    Ok(())
+}
+
+
+/// Find the number of Bits from which a Cheer is alerted as a big Cheer, or
+///     `None` if big Cheers are not alerted.
+///
+/// Return: `Option<usize>`
+pub fn big_threshold() -> Option<usize> {
+    match get_pref_int("PREF_bits_alert") {
+        Some(n) if n <= 0 => None,
+        Some(n) => Some(n as usize),
+        None => Some(BIG_DEFAULT),
+    }
+}
+
+
+#[cfg(test)]
+mod tests_bits {
+    use super::*;
+
+    /// Test to confirm that Cheers are summed per User and per Channel, and
+    ///     ranked correctly.
+    #[test]
+    fn test_leaderboard() {
+        let mut bits = Bits::default();
+
+        bits.add("#zxcv", "asdf", 100);
+        bits.add("#zxcv", "qwert", 500);
+        bits.add("#zxcv", "ASDF", 400);
+        bits.add("#zxcv", "bbb", 1);
+        bits.add("#other", "asdf", 10_000);
+
+        assert_eq!(
+            vec![("asdf", 500), ("qwert", 500)],
+            bits.leaderboard("#ZXCV", 2),
+            "Leaderboard ranked incorrectly.",
+        );
+        assert!(bits.leaderboard("#none", 10).is_empty());
+    }
+
+    /// Test to confirm that the Bits file is read back into the same totals,
+    ///     and that broken lines are skipped.
+    #[test]
+    fn test_load() {
+        let mut bits = Bits::default();
+        let lines: Vec<String> = vec![
+            String::from("#zxcv asdf 100"),
+            String::from("#zxcv asdf 250"),
+            String::from("#zxcv qwert lots"),
+            String::from(""),
+        ];
+
+        assert_eq!(2, bits.load(&lines), "Wrong number of lines used.");
+        assert_eq!(vec![("asdf", 350)], bits.leaderboard("#zxcv", 10));
+    }
+
+    /// Test to confirm that the totals are written back one line for each User,
+    ///     keeping only the highest.
+    #[test]
+    fn test_lines() {
+        let mut bits = Bits::default();
+
+        bits.add("#zxcv", "asdf", 100);
+        bits.add("#zxcv", "asdf", 250);
+        bits.add("#zxcv", "qwert", 5);
+        bits.add("#other", "qwert", 1_000);
+
+        assert_eq!(vec!["#other qwert 1000", "#zxcv asdf 350"], bits.lines(2),
+                   "Totals trimmed incorrectly.");
+
+        let mut back = Bits::default();
+        assert_eq!(3, back.load(&bits.lines(LOG_LIMIT)), "Lines not read back.");
+        assert_eq!(bits.leaderboard("#zxcv", 10), back.leaderboard("#zxcv", 10));
+    }
+}
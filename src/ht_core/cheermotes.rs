@@ -0,0 +1,163 @@
+//! Module for Cheermotes: Words in a Message like `Cheer100`, which are shown
+//!     by Twitch as animated images, and which carry the Bits of the Message.
+
+use hexchat::get_pref_int;
+
+
+/// PREFIXES: The names of the global Cheermotes. A Cheermote is one of these,
+///     in any case, followed directly by a number of Bits.
+static PREFIXES: &[&str] = &[
+    "4head", "anon", "bibblethump", "biblethump", "bitboss", "charity",
+    "cheer", "cheerwhal", "corgo", "dansgame", "doodlecheer", "elegiggle",
+    "failfish", "frankerz", "heyguys", "holidaycheer", "kappa", "kreygasm",
+    "mrdestructoid", "muxy", "notlikethis", "party", "pjsalt", "pride",
+    "ripcheer", "scoops", "seemsgood", "shamrock", "showlove", "streamlabs",
+    "swiftrage", "trihard", "uni", "vohiyo",
+];
+
+/// TIERS: IRC colors for Cheermotes, by the lowest number of Bits in a tier.
+/// NOTE: if any value here is not greater than the previous one, it and
+///     subsequent pairs will not be considered in the correct order.
+static TIERS: &[(usize, &str)] = &[
+    (1, "14"),
+    (100, "06"),
+    (1_000, "10"),
+    (5_000, "12"),
+    (10_000, "04"),
+];
+
+
+/// Mode: How Cheermotes in a Message are shown.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// Leave the Message unchanged.
+    Plain,
+    /// Replace the Cheermotes with one marker of their total, like `[100 bits]`.
+    Compact,
+    /// Color each Cheermote by its tier.
+    Tiers,
+}
+
+impl Mode {
+    /// Find the Mode chosen in the Preferences. Defaults to `Compact`.
+    ///
+    /// Return: `Mode`
+    pub fn current() -> Self {
+        match get_pref_int("PREF_cheermotes") {
+            Some(0) => Self::Plain,
+            Some(2) => Self::Tiers,
+            _ => Self::Compact,
+        }
+    }
+}
+
+
+/// Find the number of Bits in a word, if the word is a Cheermote.
+///
+/// Input: `&str`
+/// Return: `Option<usize>`
+pub fn parse(word: &str) -> Option<usize> {
+    let split = word.find(|c: char| c.is_ascii_digit())?;
+    let (prefix, amount) = word.split_at(split);
+
+    if !amount.bytes().all(|b| b.is_ascii_digit())
+        || !PREFIXES.iter().any(|p| p.eq_ignore_ascii_case(prefix))
+    {
+        return None;
+    }
+
+    amount.parse().ok().filter(|&n| n > 0)
+}
+
+
+/// Find the IRC color for a Cheermote of a number of Bits.
+///
+/// Input: `usize`
+/// Return: `&str`
+fn tier(bits: usize) -> &'static str {
+    let mut out = TIERS[0].1;
+
+    for &(min, color) in TIERS {
+        if min <= bits { out = color; } else { break; }
+    }
+
+    out
+}
+
+
+/// Rewrite the Cheermotes in the text of a Message. The Cheermotes are only
+///     rewritten if their Bits add up to the number in the `bits` Tag, since
+///     otherwise some of the words found may not really be Cheermotes.
+///     Returns `None` if the text should be left unchanged.
+///
+/// Input: `&str`, `usize`, `Mode`
+/// Return: `Option<String>`
+pub fn render(text: &str, bits: usize, mode: Mode) -> Option<String> {
+    if mode == Mode::Plain { return None; }
+
+    let words: Vec<(&str, Option<usize>)> = text.split(' ')
+        .map(|word| (word, parse(word)))
+        .collect();
+    let total: usize = words.iter().filter_map(|&(_, n)| n).sum();
+
+    if total == 0 || total != bits { return None; }
+
+    let mut out: Vec<String> = Vec::with_capacity(words.len());
+    let mut marked = false;
+
+    for (word, amount) in words {
+        match (amount, mode) {
+            (None, _) => out.push(word.to_owned()),
+            (Some(n), Mode::Tiers) => out.push(format!("\x03{}{}\x0F", tier(n), word)),
+            (Some(_), _) if !marked => {
+                out.push(format!(
+                    "\x0306[{} bit{}]\x0F",
+                    total,
+                    if total == 1 { "" } else { "s" },
+                ));
+                marked = true;
+            }
+            (Some(_), _) => {}
+        }
+    }
+
+    Some(out.join(" "))
+}
+
+
+#[cfg(test)]
+mod tests_cheermotes {
+    use super::*;
+
+    /// Test to confirm that only known prefixes with an amount are Cheermotes.
+    #[test]
+    fn test_parse() {
+        assert_eq!(Some(100), parse("Cheer100"));
+        assert_eq!(Some(5), parse("cHeEr5"));
+        assert_eq!(Some(1000), parse("DoodleCheer1000"));
+        assert_eq!(None, parse("Cheer"), "Cheermote without an amount.");
+        assert_eq!(None, parse("Cheer0"), "Cheermote of zero Bits.");
+        assert_eq!(None, parse("Cheer10x"), "Cheermote with trailing text.");
+        assert_eq!(None, parse("Asdf100"), "Cheermote with unknown prefix.");
+        assert_eq!(None, parse("100"), "Cheermote without a prefix.");
+    }
+
+    /// Test to confirm that Cheermotes are rewritten in each Mode, and only if
+    ///     they add up to the `bits` Tag.
+    #[test]
+    fn test_render() {
+        let text = "Cheer100 hello Kappa23 Cheer100";
+
+        assert_eq!(
+            Some(String::from("\x0306[223 bits]\x0F hello")),
+            render(text, 223, Mode::Compact),
+        );
+        assert_eq!(
+            Some(String::from("\x0306Cheer100\x0F hello \x0314Kappa23\x0F \x0306Cheer100\x0F")),
+            render(text, 223, Mode::Tiers),
+        );
+        assert_eq!(None, render(text, 223, Mode::Plain));
+        assert_eq!(None, render(text, 200, Mode::Compact), "Rewritten with wrong total.");
+        assert_eq!(None, render("hello", 0, Mode::Compact), "Rewritten without Cheermotes.");
+    }
+}
@@ -1,39 +1,73 @@
-use std::fmt::Write;
+use std::{fmt::Write, time::Instant};
 
 use hexchat::{
     ChannelRef,
     EatMode,
     get_channel,
     get_channel_name,
+    get_current_channel,
+    get_focused_channel,
+    get_network_name,
     get_pref_int,
     get_pref_string,
+    print_event,
     print_event_to_channel,
     print_plain,
     PrintEvent,
     send_command,
+    set_current_channel,
+    strip_formatting,
 };
 
 use super::{
+    bits,
+    gifts,
     irc::{Message, split_at_char},
+    notices,
+    outgoing,
+    profiles::{Action, PROFILES},
+    raids::RAIDS,
+    ROOM_IDS,
+    whispers::{self, Route},
     output::{
+        Category,
         echo,
+        echo_at,
         EVENT_ALERT,
         EVENT_CHANNEL,
         EVENT_ERR,
-        EVENT_NORMAL,
         EVENT_REWARD,
+        in_channel,
         USERSTATE,
     },
 };
 
 
-pub fn cheer(name: &str, number: usize) {
+/// Announce a Cheer, and add it to the total of the User in the Channel. A
+///     Cheer of at least the threshold set in the Preferences is alerted, and
+///     colors the Tab as a big Cheer.
+///
+/// Input: `&str`, `&str`, `usize`
+pub fn cheer(channel: &str, name: &str, number: usize) {
     if number > 0 {
-        echo(EVENT_REWARD, &[
-            "CHEER",
-            &format!("{} cheers", name),
-            &format!("{} bit{}", number, if number == 1 { "" } else { "s" }),
-        ], 1)
+        let total = bits::BITS.write().add(channel, name, number);
+
+        if let Err(e) = bits::keep(channel, name, number) {
+            echo(EVENT_ERR, &[format!("Failed to keep Bits: {}", e)], 0);
+        }
+
+        if bits::big_threshold().is_some_and(|big| number >= big) {
+            echo(EVENT_ALERT, &[
+                "BIG CHEER",
+                &format!("{} cheers {} bits, for {} in total", name, number, total),
+            ], Category::BigCheer.color());
+        } else {
+            echo(EVENT_REWARD, &[
+                "CHEER",
+                &format!("{} cheers", name),
+                &format!("{} bit{}", number, if number == 1 { "" } else { "s" }),
+            ], Category::Cheer.color());
+        }
     }
 }
 
@@ -47,7 +81,7 @@ pub fn reward(word: &[String], msg: &Message) -> Option<EatMode> {
                 &notif,
                 &format!("{}:", msg.author()),
                 &word[1],
-            ], 2);
+            ], Category::Reward.color());
         } else {
             //  We do NOT know what it should be called. Use a generic "CUSTOM"
             //      label, and also print the ID.
@@ -55,7 +89,7 @@ pub fn reward(word: &[String], msg: &Message) -> Option<EatMode> {
                 "CUSTOM",
                 &format!("({}) {}:", custom, msg.author()),
                 &word[1],
-            ], 2);
+            ], Category::Reward.color());
         }
 
         Some(EatMode::All)
@@ -63,7 +97,7 @@ pub fn reward(word: &[String], msg: &Message) -> Option<EatMode> {
         echo(EVENT_ALERT, &[
             msg.author(),
             &word[1],
-        ], 2);
+        ], Category::Reward.color());
 
         Some(EatMode::All)
     } else { None }
@@ -73,6 +107,11 @@ pub fn reward(word: &[String], msg: &Message) -> Option<EatMode> {
 pub fn roomstate(msg: Message) -> Option<EatMode> {
     let tags = msg.tags.as_ref()?;
 
+    //  Remember the ID of the Channel, for requests to the API.
+    if let (Some(id), Some(channel)) = (tags.get("room-id"), msg.args.first()) {
+        ROOM_IDS.write().insert(channel.to_ascii_lowercase(), id.to_owned());
+    }
+
     //  Only report this Message if it seems to be an On-Join update.
     if tags.len() <= 2 { return Some(EatMode::Hexchat); }
 
@@ -129,206 +168,113 @@ pub fn roomstate(msg: Message) -> Option<EatMode> {
 
 pub fn usernotice(msg: Message) -> Option<EatMode> {
     let stype = msg.get_tag("msg-id")?;
+    let time = msg.time();
 
-    match stype.as_str() {
-        "raid" => {
-            echo(EVENT_NORMAL, &[format!(
-                "A raid of {} arrives from #{}",
-                msg.get_tag("msg-param-viewerCount")?,
-                msg.get_tag("msg-param-displayName")?.to_lowercase(),
-            )], 1);
-        }
-        "bitsbadgetier" | "charity" | "rewardgift" | "ritual" => {
-            echo(EVENT_NORMAL, &[msg.get_tag("system-msg")?], 1);
-        }
-
-        "unraid" => { echo(EVENT_NORMAL, &["A raid has been canceled"], 1); }
-
-        "sub" | "resub" => {
-            // Maximum possible usage should be 362 bytes; 384=256+128
-            let mut line = String::with_capacity(384);
-            write!(&mut line, "<{}> {}scribes", msg.get_tag("login")?, stype).ok()?;
-
-            if let Some(plan) = msg.get_tag("msg-param-sub-plan") {
-                match plan.as_str() {
-                    "Prime" => { line.push_str(" with Prime") }
-                    "1000" => { /*line.push_str(" at Tier 1 ($5)")*/ }
-                    "2000" => { line.push_str(" at Tier 2 ($10)") }
-                    "3000" => { line.push_str(" at Tier 3 ($25)") }
-                    plan => { write!(&mut line, " with plan {:?}", plan).ok()? }
-                };
-            }
+    if !notices::is_known(&stype) && get_pref_int("PREF_htdebug").unwrap_or(0) != 0 {
+        echo_at(EVENT_ERR, &time, &[format!(
+            "Unknown SType '{}': {}",
+            stype, msg,
+        )], 1);
+    }
 
-            if let Some(streak) = msg.get_tag("msg-param-streak-months") {
-                if streak.parse().unwrap_or(0) > 1 {
-                    write!(&mut line, " for ({}) months in a row", streak).ok()?;
-                }
-            }
+    if get_pref_int("PREF_giftbomb_expand").unwrap_or(0) == 0
+        && gift_bomb(&stype, &msg)
+    {
+        //  This Gift is part of a Bomb, and will be listed in its summary.
+        return Some(EatMode::Hexchat);
+    }
 
-            if let Some(cumul) = msg.get_tag("msg-param-cumulative-months") {
-                if cumul.parse().unwrap_or(0) > 1 {
-                    write!(&mut line, ", with ({}) months in total", cumul).ok()?;
-                }
-            }
+    let line = match notices::render(&msg, &notices::TEMPLATES.read()) {
+        Some(line) => line,
+        //  Unknown, with no `system-msg` to show. Nothing can be done.
+        None if !notices::is_known(&stype) => return Some(EatMode::Hexchat),
+        //  Known, but missing a Tag the template needs.
+        None => return None,
+    };
+    let color = line.category.map_or(1, |(cat, level)| cat.color_or(level));
 
-            if !msg.trail.is_empty() { write!(&mut line, ": {}", msg.trail).ok()?; }
+    match &line.label {
+        Some(label) => echo_at(line.event, &time, &[label, &line.text], color),
+        None => echo_at(line.event, &time, &[&line.text], color),
+    }
 
-            echo(EVENT_ALERT, &["SUBSCRIPTION", &line], 2);
-        }
+    if stype == "raid" { raided(&msg); }
 
-        "extendsub" => {
-            // Maximum possible usage should be 384 bytes.
-            let mut line = String::with_capacity(384);
-            write!(&mut line, "<{}> extends a sub", msg.get_tag("login")?).ok()?;
-
-            if let Some(plan) = msg.get_tag("msg-param-sub-plan") {
-                match plan.as_str() {
-                    "Prime" => { line.push_str(" with Prime") }
-                    "1000" => { /*line.push_str(" at Tier 1 ($5)")*/ }
-                    "2000" => { line.push_str(" at Tier 2 ($10)") }
-                    "3000" => { line.push_str(" at Tier 3 ($25)") }
-                    plan => { write!(&mut line, " with plan {:?}", plan).ok()? }
-                };
-            }
+    Some(EatMode::Hexchat)
+}
 
-            if let Some(streak) = msg.get_tag("msg-param-streak-months") {
-                if streak.parse().unwrap_or(0) > 1 {
-                    write!(&mut line, " for ({}) months in a row", streak).ok()?;
-                }
-            }
 
-            if let Some(cumul) = msg.get_tag("msg-param-cumulative-months") {
-                if cumul.parse().unwrap_or(0) > 1 {
-                    write!(&mut line, ", with ({}) months in total", cumul).ok()?;
-                }
-            }
+/// Follow up on a Raid. The Raid is recorded, so that the Users arriving with
+///     it can be listed, and if a greeting template is set in the Preferences,
+///     it is filled and sent to the Channel. The greeting is only sent if the
+///     User is a Moderator of the Channel.
+///
+/// Input: `&Message`
+fn raided(msg: &Message) {
+    let channel = match msg.args.first() {
+        Some(channel) => channel,
+        None => return,
+    };
+    let templates = notices::TEMPLATES.read();
+
+    RAIDS.write().raid(
+        channel,
+        &templates.fill_with("{raider}", msg).unwrap_or_default(),
+        msg.get_tag("msg-param-viewerCount")
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(0),
+        Instant::now(),
+    );
 
-            if let Some(month) = msg.get_tag("msg-param-sub-benefit-end-month") {
-                write!(&mut line, ", through {}", match month.as_str() {
-                    "1" => "January",
-                    "2" => "February",
-                    "3" => "March",
-                    "4" => "April",
-                    "5" => "May",
-                    "6" => "June",
-                    "7" => "July",
-                    "8" => "August",
-                    "9" => "September",
-                    "10" => "October",
-                    "11" => "November",
-                    "12" => "December",
-                    other => other,
-                }).ok()?;
+    if let Some(greeting) = get_pref_string("PREF_raid_greeting")
+        .filter(|g| !g.is_empty())
+    {
+        if USERSTATE.read().is_mod(channel) {
+            if let Some(text) = templates.fill_with(&greeting, msg) {
+                outgoing::send(channel, format!("SAY {}", text));
             }
-
-            if !msg.trail.is_empty() { write!(&mut line, ": {}", msg.trail).ok()?; }
-
-            echo(EVENT_ALERT, &["SUBSCRIPTION", &line], 2);
         }
+    }
+}
 
-        "subgift" => {
-            let mut line = String::with_capacity(137);
-            write!(
-                &mut line,
-                "<{}> is gifted a subscription by <{}>",
-                msg.get_tag("msg-param-recipient-user-name")?,
-                msg.get_tag("login")?,
-            ).ok()?;
-
-            if let Some(streak) = msg.get_tag("msg-param-months") {
-                if streak.parse().unwrap_or(0) > 1 {
-                    write!(&mut line, " for ({}) months in a row", streak).ok()?;
-                }
-            }
 
-            if let Some(cumul) = msg.get_tag("msg-param-cumulative-months") {
-                if cumul.parse().unwrap_or(0) > 1 {
-                    write!(&mut line, ", with ({}) months in total", cumul).ok()?;
-                }
-            }
+/// Track the Gifts of a Gift Bomb. A `submysterygift` opens a new Bomb, and is
+///     still printed; A `subgift` belonging to an open Bomb is added to it, and
+///     is NOT printed. Returns `true` if the UserNotice should not be printed.
+///
+/// Input: `&str`, `&Message`
+/// Return: `bool`
+fn gift_bomb(stype: &str, msg: &Message) -> bool {
+    let channel = match msg.args.first() {
+        Some(channel) => channel,
+        None => return false,
+    };
+    let sender = msg.get_tag("login").unwrap_or_default();
+    let origin = msg.get_tag("msg-param-origin-id");
 
-            echo(EVENT_ALERT, &["SUBSCRIPTION", &line], 2);
-        }
+    match stype {
         "submysterygift" => {
-            let num = msg.get_tag("msg-param-mass-gift-count")?;
-            echo(EVENT_ALERT, &["SUBSCRIPTION", &format!(
-                "<{}> gives out ({}) random gift subscription{}",
-                msg.get_tag("login")?, num,
-                if &num == "1" { "" } else { "s" },
-            )], 2);
-        }
-        "standardpayforward" => {
-            if let Some(prior) = msg.get_tag("msg-param-prior-gifter-user-name") {
-                echo(EVENT_NORMAL, &[format!(
-                    "<{}> pays forward a gift subscription from <{}> to <{}>",
-                    msg.get_tag("login")?,
-                    prior,
-                    msg.get_tag("msg-param-recipient-user-name")?,
-                )], 1);
-            } else {
-                echo(EVENT_NORMAL, &[format!(
-                    "<{}> pays forward an anonymous gift subscription to <{}>",
-                    msg.get_tag("login")?,
-                    msg.get_tag("msg-param-recipient-user-name")?,
-                )], 1);
-            }
-        }
-        "communitypayforward" => {
-            if let Some(prior) = msg.get_tag("msg-param-prior-gifter-user-name") {
-                echo(EVENT_NORMAL, &[format!(
-                    "<{}> pays forward a gift subscription from <{}> to the community",
-                    msg.get_tag("login")?,
-                    prior,
-                )], 1);
-            } else {
-                echo(EVENT_NORMAL, &[format!(
-                    "<{}> pays forward an anonymous gift subscription to the community",
-                    msg.get_tag("login")?,
-                )], 1);
-            }
-        }
-
-        "giftpaidupgrade" => {
-            echo(EVENT_ALERT, &["UPGRADE", &format!(
-                "<{}> upgrades a gift subscription from <{}>",
-                msg.get_tag("login")?,
-                msg.get_tag("msg-param-sender-login")?,
-            )], 2);
-        }
-        "anongiftpaidupgrade" => {
-            echo(EVENT_ALERT, &["UPGRADE", &format!(
-                "<{}> upgrades an anonymous gift subscription",
-                msg.get_tag("login")?,
-            )], 2);
-        }
-        "primepaidupgrade" => {
-            echo(EVENT_ALERT, &["UPGRADE", &format!(
-                "<{}> upgrades a Prime subscription",
-                msg.get_tag("login")?,
-            )], 2);
-        }
-
-        // "bitsbadgetier" => {
-        //     echo(EVENT_ALERT, &["BITS BADGE", &format!(
-        //         "<{}> earns a new tier of Bits Badge",
-        //         msg.get_tag("login")?,
-        //     )], 1);
-        // }
-
-        _ => {
-            if get_pref_int("PREF_htdebug").unwrap_or(0) != 0 {
-                echo(EVENT_ERR, &[format!(
-                    "Unknown SType '{}': {}",
-                    stype, msg,
-                )], 1);
-            }
-
-            if let Some(sysmsg) = msg.get_tag("system-msg") {
-                echo(EVENT_ALERT, &["UNKNOWN", &sysmsg], 1);
+            let count = msg.get_tag("msg-param-mass-gift-count")
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0);
+
+            if count > 1 {
+                gifts::BOMBS.lock().open(
+                    channel, &sender, origin, count, Instant::now(),
+                );
             }
+            false
         }
+        "subgift" => match msg.get_tag("msg-param-recipient-display-name")
+            .or_else(|| msg.get_tag("msg-param-recipient-user-name"))
+        {
+            Some(recipient) => gifts::BOMBS.lock().gift(
+                channel, &sender, origin.as_deref(), &recipient, Instant::now(),
+            ),
+            None => false,
+        },
+        _ => false,
     }
-    Some(EatMode::Hexchat)
 }
 
 
@@ -354,7 +300,7 @@ pub fn userstate(msg: Message) -> Option<EatMode> {
 
 /// Ensure Tab: Given a Channel Name, try to find it in the Twitch Network. If
 ///     it is not found, run the Hexchat Command to open it. Then, try to find
-///     it again.
+///     it again. A newly opened Tab has recent Whispers replayed into it.
 ///
 /// Input: `&str`
 /// Return: `ChannelRef`
@@ -364,106 +310,199 @@ pub fn ensure_tab(name: &str) -> ChannelRef {
         Some(check) => { check }
         None => {
             send_command(&format!("QUERY {}", &name));
-            get_channel("Twitch", &name).expect("Failed to ensure Whisper Tab.")
+            let tab = get_channel("Twitch", &name)
+                .expect("Failed to ensure Whisper Tab.");
+
+            //  Tabs like `(whispers)` do not belong to a User, and have no log.
+            if !name.starts_with('(') { whispers::replay(name, &tab); }
+            tab
         }
     }
 }
 
 
-/// Receive an IRC Message as a Twitch Whisper. The Message will be edited
-///     somewhat, so that HexChat parses it in the right way.
+/// Add a Whisper to the log of a conversation, reporting any failure.
 ///
-/// NOTE: If this Function is given a Message whose Command field value is less
-///     than 7 bytes, that `String` will reallocate.
+/// Input: `&str`, `&str`, `&str`
+fn log_whisper(partner: &str, author: &str, text: &str) {
+    if let Err(e) = whispers::record(partner, &whispers::Entry::new(author, text)) {
+        echo(EVENT_ERR, &[format!("Failed to log Whisper: {}", e)], 0);
+    }
+}
+
+
+/// Receive an IRC Message as a Twitch Whisper. The Whisper is printed into the
+///     Tab of its author, with the Badges and Display Name from its Tags, and
+///     the Topic of the Tab is updated to describe the author. A Whisper from
+///     an author with the `Ignore` Route is only logged.
 ///
 /// Input: `Message`
 /// Return: `Option<EatMode>`
-pub fn whisper_recv(mut msg: Message) -> Option<EatMode> {
-    let etype: PrintEvent;
-    let user = msg.prefix.name();
-
-    //  Swap out fields of the Message to reshape it into one that HexChat can
-    //      nicely handle for us.
-    //  The Command is (fragilely) guaranteed to have previously been "WHISPER",
-    //      which is the same length as "PRIVMSG", so we can probably avoid an
-    //      allocation by overwriting it.
-    "PRIVMSG".clone_into(&mut msg.command);
-
-    //  Change the first Argument to be the name of the author.
-    msg.args[0] = user.to_owned();
-
-    //  Action Messages have a different format than simply a `/me` command. For
-    //      example, the command "/me does something" would have to be changed
-    //      to "\x01ACTION does something\x01".
-    if msg.trail.starts_with("/me ") {
-        etype = PrintEvent::PRIVATE_ACTION;
-        let text: &str = &msg.trail[4..]; // Slice off the `/me `.
-
-        //  If the Whisper Tab is not focused, also post it here.
-        if get_pref_int("PREF_whispers_in_current").unwrap_or(0) != 0
-            && get_channel_name() != user
-        {
-            echo(etype, &[user, text], 2);
-        }
+pub fn whisper_recv(msg: Message) -> Option<EatMode> {
+    let user = msg.author();
+    let route = Route::for_user(user);
 
-        //  Format the sliced text into an Action Message and replace the Trail.
-        msg.trail = format!("\x01ACTION {}\x01", &text);
-    } else {
-        etype = PrintEvent::PRIVATE_MESSAGE;
+    if route == Route::Ignore {
+        log_whisper(user, user, &msg.trail);
+        return Some(EatMode::All);
+    }
 
-        //  If the Whisper Tab is not focused, also post it here.
-        if get_pref_int("PREF_whispers_in_current").unwrap_or(0) != 0
-            && get_channel_name() != user
-        {
-            echo(etype, &[user, &msg.trail], 2);
+    //  Make sure the Tab exists before the Whisper is logged, so that it is not
+    //      replayed into the Tab as history.
+    ensure_tab(user);
+    log_whisper(user, user, &msg.trail);
+
+    //  Take what is needed from the Partner, so that the lock is not held while
+    //      HexChat runs Commands and prints, which may call back into hooks.
+    let (display, badges, topic) = {
+        let mut partners = whispers::PARTNERS.write();
+        let changed = partners.update(&msg);
+        let partner = partners.get(user)?;
+
+        (partner.nick(), partner.badges.output.clone(), changed.then(|| partner.topic()))
+    };
+
+    if let Some(topic) = topic {
+        send_command(&format!("RECV :Twitch@twitch.tv TOPIC {} :{}", user, topic));
+    }
+
+    let color = Category::Whisper.color();
+
+    //  Action Messages are sent by Twitch with the same `/me` prefix that was
+    //      typed. Slice it off, and print it as an Action.
+    let (etype, etype_dm, text) = match msg.trail.strip_prefix("/me ") {
+        Some(action) => (
+            PrintEvent::PRIVATE_ACTION,
+            PrintEvent::PRIVATE_ACTION_TO_DIALOG,
+            action,
+        ),
+        None => (
+            PrintEvent::PRIVATE_MESSAGE,
+            PrintEvent::PRIVATE_MESSAGE_TO_DIALOG,
+            msg.trail.as_str(),
+        ),
+    };
+
+    in_channel(user, || echo(etype_dm, &[
+        display.as_str(),
+        text,
+        badges.as_str(),
+    ], color));
+
+    match route {
+        Route::Always if get_channel_name() != user => {
+            //  The Whisper Tab is not current; Also post it here.
+            echo(etype, &[&display, text], color);
         }
+        Route::Unfocused => {
+            //  If the focused Tab is not on Twitch, also post it there.
+            if let Some(focused) = get_focused_channel() {
+                let prev = get_current_channel();
+
+                if set_current_channel(&focused) {
+                    if !get_network_name().unwrap_or_default()
+                        .eq_ignore_ascii_case("twitch")
+                    {
+                        print_event(etype, &[&display, text]);
+                    }
+                    set_current_channel(&prev);
+                }
+            }
+        }
+        Route::Aggregate => {
+            print_event_to_channel(&ensure_tab(whispers::AGGREGATE_TAB), etype_dm, &[
+                display.as_str(),
+                text,
+                badges.as_str(),
+            ]);
+        }
+        _ => {}
     }
 
-    send_command(&format!("RECV {}", msg));
     Some(EatMode::All)
 }
 
 
+/// Send a Whisper typed by the User into a Whisper Tab. The Tab is named for
+///     the conversation partner.
+///
+/// Input: `PrintEvent`, `&str`, `&[String]`
 pub fn whisper_send(etype: PrintEvent, channel: &str, word: &[String]) {
-    //  "asdf qwert" normal -> exec SAY ".w asdf qwert"
-    //  ".w asdf qwert" -> emit "asdf qwert" as private
-
-    let text: &str = &word[1];
+    let sent = if etype == PrintEvent::YOUR_ACTION {
+        whispers::send(channel, &format!("/me {}", word[1]))
+    } else {
+        whispers::send(channel, &word[1])
+    };
 
-    if text.starts_with(".w ") {
-        //  Normal Message, begins with ".w". The Whisper has been sent. Print
-        //      the message in the Tab.
-        let mut iter = text[3..].trim().splitn(2, " ");
-        let user: &str = iter.next().unwrap();
+    if !sent {
+        echo(EVENT_ERR, &["Whispers can only be sent after joining a Channel."], 0);
+    }
+}
 
-        if let Some(mut text) = iter.next() {
-            if user != channel {
-                echo(PrintEvent::MESSAGE_SEND, &[&user, &text], 2);
-            }
 
-            let etype_dm: PrintEvent = match etype {
-                PrintEvent::YOUR_ACTION => PrintEvent::PRIVATE_ACTION_TO_DIALOG,
-                PrintEvent::YOUR_MESSAGE if text.starts_with("/me ") => {
-                    text = &text[4..];
-                    PrintEvent::PRIVATE_ACTION_TO_DIALOG
-                }
-                PrintEvent::YOUR_MESSAGE => PrintEvent::PRIVATE_MESSAGE_TO_DIALOG,
-                _ => PrintEvent::PRIVATE_MESSAGE_TO_DIALOG,
-            };
+/// Handle the echo of a Whisper Command, whether it was sent by the Plugin or
+///     typed directly by the User. The Whisper has already been sent, so it is
+///     only printed into its Tab and logged; It is NEVER sent again.
+///
+/// Input: `PrintEvent`, `&str`, `&[String]`, `&str`, `&str`
+pub fn whisper_echo(
+    etype: PrintEvent,
+    channel: &str,
+    word: &[String],
+    user: &str,
+    mut text: &str,
+) {
+    whispers::OUTBOX.lock().echo(user, text, Instant::now());
+
+    if !user.eq_ignore_ascii_case(channel) {
+        echo(PrintEvent::MESSAGE_SEND, &[&user, &text], Category::Whisper.color());
+    }
 
-            print_event_to_channel(&ensure_tab(user), etype_dm, &[
-                word[0].as_str(), text, word[2].as_str(),
-            ]);
+    let etype_dm: PrintEvent = match etype {
+        PrintEvent::YOUR_ACTION => PrintEvent::PRIVATE_ACTION_TO_DIALOG,
+        PrintEvent::YOUR_MESSAGE if text.starts_with("/me ") => {
+            text = &text[4..];
+            PrintEvent::PRIVATE_ACTION_TO_DIALOG
         }
+        _ => PrintEvent::PRIVATE_MESSAGE_TO_DIALOG,
+    };
+
+    let tab = ensure_tab(user);
+    let me = strip_formatting(&word[0]).unwrap_or_else(|| word[0].clone());
+
+    if etype_dm == PrintEvent::PRIVATE_ACTION_TO_DIALOG {
+        log_whisper(user, &me, &format!("/me {}", text));
     } else {
-        //  Normal Message, does NOT begin with ".w". Need to send the Whisper.
-        //      Execute SAY on the message with ".w" prepended.
-        if etype == PrintEvent::YOUR_ACTION {
-            send_command(&format!("SAY .w {} /me {}", &channel, text));
-        } else {
-            send_command(&format!("SAY .w {} {}", &channel, text));
+        log_whisper(user, &me, text);
+    }
+
+    print_event_to_channel(&tab, etype_dm, &[
+        word[0].as_str(), text, word[2].as_str(),
+    ]);
+}
+
+
+/// Handle a Notice from the Server. Notices reporting a failed Whisper are
+///     matched to the Whisper that failed, and reported in its Tab.
+///
+/// Input: `Message`
+/// Return: `Option<EatMode>`
+pub fn notice(msg: Message) -> Option<EatMode> {
+    let id = msg.get_tag("msg-id").unwrap_or_default();
+
+    if id.starts_with("whisper_") {
+        if let Some((user, text)) = whispers::OUTBOX.lock().fail(Instant::now()) {
+            if let Some(tab) = get_channel("Twitch", &user) {
+                print_event_to_channel(&tab, EVENT_ERR, &[format!(
+                    "Whisper NOT sent: {} ({})",
+                    text,
+                    msg.trail,
+                )]);
+            }
         }
     }
+
+    Some(EatMode::None)
 }
 
 
@@ -485,7 +524,7 @@ pub fn hosttarget(msg: Message) -> Option<EatMode> {
         echo(
             EVENT_CHANNEL,
             &[&hashtarg, &format!("https://twitch.tv/{}", target)],
-            1,
+            Category::Raid.color(),
         );
 
         if let Some(channel) = get_channel("Twitch", &hashtarg) {
@@ -502,11 +541,15 @@ pub fn hosttarget(msg: Message) -> Option<EatMode> {
 
 
 pub fn clearmsg(msg: Message) -> Option<EatMode> {
+    if let (Some(channel), Some(login)) = (msg.args.first(), msg.get_tag("login")) {
+        PROFILES.write().deleted(channel, &login);
+    }
+
     echo(
         EVENT_ERR,
         &[format!("A message by <{}> is deleted: {}",
                   msg.get_tag("login")?, &msg.trail)],
-        1,
+        Category::Moderation.color(),
     );
     Some(EatMode::Hexchat)
 }
@@ -525,6 +568,15 @@ pub fn clearchat(msg: Message) -> Option<EatMode> {
         }
     }
 
-    echo(EVENT_ERR, &[text], 1);
+    //  A ClearChat with no User clears the whole Channel.
+    if let (Some(channel), false) = (msg.args.first(), msg.trail.is_empty()) {
+        PROFILES.write().action(channel, &msg.trail, Action {
+            time: msg.time(),
+            duration: msg.get_tag("ban-duration").and_then(|t| t.parse().ok()),
+            reason: msg.get_tag("ban-reason").unwrap_or_default(),
+        });
+    }
+
+    echo(EVENT_ERR, &[text], Category::Moderation.color());
     Some(EatMode::Hexchat)
 }
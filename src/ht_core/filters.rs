@@ -0,0 +1,585 @@
+//! Module for rules which match Messages by their text and their Tags. Rules
+//!     are used to highlight Messages beyond the name of the User, and to hide
+//!     Messages entirely. Each set of Rules is kept in a file, one on each line.
+
+use std::{collections::VecDeque, fmt, io};
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use regex::Regex;
+
+use super::{irc::Message, storage};
+
+
+/// File in which Highlight Rules are kept.
+pub const HIGHLIGHTS_FILE: &str = "highlights.conf";
+/// Tab into which highlighted Messages may be copied.
+pub const HIGHLIGHTS_TAB: &str = "(highlights)";
+/// File in which Ignore Rules are kept.
+pub const IGNORES_FILE: &str = "ignores.conf";
+/// Number of hidden Messages kept, to be shown on request.
+pub const HIDDEN_LIMIT: usize = 50;
+
+/// Badges of Users who run a Channel, or Twitch itself. These Users are never
+///     matched for lacking some other Badge.
+const PRIVILEGED: &[&str] = &["broadcaster", "moderator", "staff", "admin", "global_mod"];
+
+/// Top-level domains which are taken as links without a scheme or `www.`. Any
+///     word could end in something like a domain, so only those commonly seen
+///     in chat are listed, leaving out those which double as file extensions.
+const TLDS: &[&str] = &[
+    "ai", "app", "au", "be", "blog", "br", "ca", "cc", "ch", "club", "cn", "co",
+    "com", "de", "dev", "edu", "es", "eu", "fm", "fr", "gg", "gl", "gov", "info",
+    "io", "it", "jp", "kr", "link", "live", "ly", "me", "net", "news", "nl",
+    "online", "org", "pl", "ru", "se", "shop", "site", "store", "to", "tv", "uk",
+    "us", "xyz",
+];
+
+
+/// Matcher: A test of one property of a Message.
+#[derive(Clone, Debug)]
+pub enum Matcher {
+    /// The text contains a phrase, in any case.
+    Text(String),
+    /// The text matches a Regular Expression.
+    Regex(Regex),
+    /// The Message was sent by a User, by login name.
+    Author(String),
+    /// The Message was sent by a User, by ID, which survives a change of name.
+    UserId(String),
+    /// The author has a Badge, such as `moderator`.
+    Badge(String),
+    /// The author does NOT have a Badge. The Broadcaster, Moderators, and
+    ///     Twitch staff are never matched.
+    NoBadge(String),
+    /// The Message carries at least a number of Bits.
+    Bits(usize),
+    /// The Message is the first ever sent by its author in the Channel.
+    FirstMsg,
+    /// The text contains a link.
+    Links,
+}
+
+impl Matcher {
+    /// The names of the kinds of Matcher, as used in Commands and files.
+    pub const KINDS: &'static [&'static str] = &[
+        "text", "regex", "author", "id", "badge", "nobadge", "bits", "first", "links",
+    ];
+
+    /// Build a Matcher of a kind from its value. The `first` and `links` kinds
+    ///     take no value.
+    ///
+    /// Input: `&str`, `&str`
+    /// Return: `Result<Matcher, String>`
+    pub fn new(kind: &str, value: &str) -> Result<Self, String> {
+        let kind = kind.to_ascii_lowercase();
+        let value = value.trim();
+
+        match kind.as_str() {
+            "first" => return Ok(Self::FirstMsg),
+            "links" => return Ok(Self::Links),
+            _ if value.is_empty() => return Err(String::from("A Matcher needs a value.")),
+            _ => {}
+        }
+
+        match kind.as_str() {
+            "text" => Ok(Self::Text(value.to_lowercase())),
+            "regex" => Regex::new(value).map(Self::Regex)
+                .map_err(|e| format!("Invalid Regex: {}", e)),
+            "author" => Ok(Self::Author(
+                value.trim_start_matches('@').to_ascii_lowercase(),
+            )),
+            "id" => if value.bytes().all(|b| b.is_ascii_digit()) {
+                Ok(Self::UserId(value.to_owned()))
+            } else {
+                Err(format!("Invalid User ID: {}", value))
+            }
+            "badge" => Ok(Self::Badge(value.to_ascii_lowercase())),
+            "nobadge" => Ok(Self::NoBadge(value.to_ascii_lowercase())),
+            "bits" => value.parse().map(Self::Bits)
+                .map_err(|_| format!("Invalid number of Bits: {}", value)),
+            _ => Err(format!(
+                "Unknown kind of Matcher. Kinds are: {}",
+                Self::KINDS.join(", "),
+            )),
+        }
+    }
+
+    /// Return: `&str`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Text(_) => "text",
+            Self::Regex(_) => "regex",
+            Self::Author(_) => "author",
+            Self::UserId(_) => "id",
+            Self::Badge(_) => "badge",
+            Self::NoBadge(_) => "nobadge",
+            Self::Bits(_) => "bits",
+            Self::FirstMsg => "first",
+            Self::Links => "links",
+        }
+    }
+
+    /// Check whether a Message passes this test. The text is given separately,
+    ///     because the Message may not carry it in the same form as printed.
+    ///
+    /// Input: `&Message`, `&str`
+    /// Return: `bool`
+    pub fn matches(&self, msg: &Message, text: &str) -> bool {
+        match self {
+            Self::Text(phrase) => text.to_lowercase().contains(phrase.as_str()),
+            Self::Regex(re) => re.is_match(text),
+            Self::Author(login) => msg.author().eq_ignore_ascii_case(login),
+            Self::UserId(id) => msg.get_tag("user-id").as_deref() == Some(id.as_str()),
+            Self::Badge(class) => has_badge(msg, class),
+            Self::NoBadge(class) => {
+                !has_badge(msg, class) && !PRIVILEGED.iter().any(|b| has_badge(msg, b))
+            }
+            Self::Bits(min) => msg.get_tag("bits")
+                .and_then(|bits| bits.parse::<usize>().ok())
+                .is_some_and(|bits| bits >= *min),
+            Self::FirstMsg => msg.get_tag("first-msg").as_deref() == Some("1"),
+            Self::Links => has_link(text),
+        }
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Text(s)
+            | Self::Author(s)
+            | Self::UserId(s)
+            | Self::Badge(s)
+            | Self::NoBadge(s)
+            => write!(f, "{} {}", self.kind(), s),
+            Self::Regex(re) => write!(f, "{} {}", self.kind(), re.as_str()),
+            Self::Bits(n) => write!(f, "{} {}", self.kind(), n),
+            Self::FirstMsg | Self::Links => f.write_str(self.kind()),
+        }
+    }
+}
+
+
+/// Check whether the author of a Message has a Badge, in any version.
+///
+/// Input: `&Message`, `&str`
+/// Return: `bool`
+pub fn has_badge(msg: &Message, class: &str) -> bool {
+    msg.get_tag("badges").unwrap_or_default()
+        .split(',')
+        .any(|badge| badge.split('/').next() == Some(class))
+}
+
+
+/// Check whether text contains a link: A word with a scheme, a word starting
+///     with `www.`, or a word which looks like a domain name, ending in one of
+///     the known top-level domains.
+///
+/// Input: `&str`
+/// Return: `bool`
+pub fn has_link(text: &str) -> bool {
+    text.split_whitespace().any(|word| {
+        let word = word.to_ascii_lowercase();
+
+        if word.contains("://") || word.starts_with("www.") { return true; }
+
+        let host = word.split('/').next().unwrap_or_default();
+        let labels: Vec<&str> = host.split('.').collect();
+
+        labels.len() > 1
+            && labels.iter().all(|l| {
+                !l.is_empty() && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+            })
+            && labels.last().is_some_and(|tld| TLDS.contains(tld))
+    })
+}
+
+
+/// Rule: A Matcher which highlights Messages, in one Channel or in all of them.
+#[derive(Clone, Debug)]
+pub struct Rule {
+    /// The Channel where the Rule applies, or `None` for every Channel.
+    pub channel: Option<String>,
+    /// The color of the Tab, or `None` to use the Highlight color.
+    pub color: Option<u8>,
+    /// Whether a matching Message is also copied into the Highlights Tab.
+    pub copy: bool,
+    pub matcher: Matcher,
+}
+
+impl Rule {
+    /// Check whether this Rule applies to a Message in a Channel.
+    ///
+    /// Input: `&str`, `&Message`, `&str`
+    /// Return: `bool`
+    pub fn matches(&self, channel: &str, msg: &Message, text: &str) -> bool {
+        self.channel.as_ref().is_none_or(|ch| ch.eq_ignore_ascii_case(channel))
+            && self.matcher.matches(msg, text)
+    }
+}
+
+impl fmt::Display for Rule {
+    /// Format this Rule into a single line for the file, of the form
+    ///     `<channel|*> <color|-> <copy|-> <kind> <value>`.
+    ///
+    /// Return: `fmt::Result`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "{} {} {} {}",
+            self.channel.as_deref().unwrap_or("*"),
+            self.color.map_or(String::from("-"), |c| c.to_string()),
+            if self.copy { "copy" } else { "-" },
+            self.matcher,
+        )
+    }
+}
+
+impl std::str::FromStr for Rule {
+    type Err = String;
+
+    /// Read a single line of the file into a Rule.
+    ///
+    /// Input: `&str`
+    /// Return: `Result<Rule, String>`
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut parts = line.trim().splitn(5, ' ');
+
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(channel), Some(color), Some(copy), Some(kind)) => Ok(Self {
+                channel: Some(channel)
+                    .filter(|&ch| ch != "*")
+                    .map(str::to_ascii_lowercase),
+                color: match color {
+                    "-" => None,
+                    n => Some(n.parse::<u8>()
+                        .map_err(|_| format!("Invalid color: {}", n))?
+                        .min(3)),
+                },
+                copy: copy == "copy",
+                matcher: Matcher::new(kind, parts.next().unwrap_or_default())?,
+            }),
+            _ => Err(format!("Incomplete Rule: {}", line)),
+        }
+    }
+}
+
+
+/// Highlights: The Rules which highlight Messages, in the order they were
+///     added. The first Rule to match a Message is used.
+#[derive(Default)]
+pub struct Highlights { rules: Vec<Rule> }
+
+impl Highlights {
+    /// Read Rules from lines. Lines which are not valid Rules are skipped.
+    ///
+    /// Input: `&[String]`
+    /// Return: `Highlights`
+    pub fn parse(lines: &[String]) -> Self {
+        Self {
+            rules: lines.iter()
+                .filter_map(|line| line.parse().ok())
+                .collect(),
+        }
+    }
+
+    /// Return: `Highlights`
+    pub fn load() -> Self {
+        Self::parse(&storage::read_lines(HIGHLIGHTS_FILE).unwrap_or_default())
+    }
+
+    /// Return: `io::Result<()>`
+    pub fn save(&self) -> io::Result<()> {
+        let lines: Vec<String> = self.rules.iter().map(Rule::to_string).collect();
+        storage::write_lines(HIGHLIGHTS_FILE, &lines)
+    }
+
+    /// Input: `Rule`
+    pub fn add(&mut self, rule: Rule) { self.rules.push(rule); }
+
+    /// Remove a Rule by its number, counting from one, as listed.
+    ///
+    /// Input: `usize`
+    /// Return: `Option<Rule>`
+    pub fn remove(&mut self, number: usize) -> Option<Rule> {
+        if 0 < number && number <= self.rules.len() {
+            Some(self.rules.remove(number - 1))
+        } else { None }
+    }
+
+    /// Return: `&[Rule]`
+    pub fn rules(&self) -> &[Rule] { &self.rules }
+
+    /// Find the first Rule which matches a Message in a Channel.
+    ///
+    /// Input: `&str`, `&Message`, `&str`
+    /// Return: `Option<&Rule>`
+    pub fn find(&self, channel: &str, msg: &Message, text: &str) -> Option<&Rule> {
+        self.rules.iter().find(|rule| rule.matches(channel, msg, text))
+    }
+}
+
+
+safe_static! {
+    pub static lazy HIGHLIGHTS: RwLock<Highlights> = RwLock::new(Highlights::load());
+}
+
+
+/// Ignore: A Matcher which hides Messages, in one Channel or in all of them.
+#[derive(Clone, Debug)]
+pub struct Ignore {
+    /// The Channel where the Rule applies, or `None` for every Channel.
+    pub channel: Option<String>,
+    pub matcher: Matcher,
+}
+
+impl fmt::Display for Ignore {
+    /// Format this Rule into a single line for the file, of the form
+    ///     `<channel|*> <kind> [<value>]`.
+    ///
+    /// Return: `fmt::Result`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.channel.as_deref().unwrap_or("*"), self.matcher)
+    }
+}
+
+impl std::str::FromStr for Ignore {
+    type Err = String;
+
+    /// Read a single line of the file into a Rule.
+    ///
+    /// Input: `&str`
+    /// Return: `Result<Ignore, String>`
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut parts = line.trim().splitn(3, ' ');
+
+        match (parts.next(), parts.next()) {
+            (Some(channel), Some(kind)) => Ok(Self {
+                channel: Some(channel)
+                    .filter(|&ch| ch != "*")
+                    .map(str::to_ascii_lowercase),
+                matcher: Matcher::new(kind, parts.next().unwrap_or_default())?,
+            }),
+            _ => Err(format!("Incomplete Rule: {}", line)),
+        }
+    }
+}
+
+
+/// Hidden: A Message which was hidden by an Ignore Rule.
+#[derive(Clone, Debug)]
+pub struct Hidden {
+    pub time: DateTime<Utc>,
+    pub channel: String,
+    pub author: String,
+    pub text: String,
+}
+
+
+/// Ignores: The Rules which hide Messages, with a count of the Messages hidden
+///     and the most recent of them.
+#[derive(Default)]
+pub struct Ignores {
+    rules: Vec<Ignore>,
+    count: usize,
+    hidden: VecDeque<Hidden>,
+}
+
+impl Ignores {
+    /// Read Rules from lines. Lines which are not valid Rules are skipped.
+    ///
+    /// Input: `&[String]`
+    /// Return: `Ignores`
+    pub fn parse(lines: &[String]) -> Self {
+        Self {
+            rules: lines.iter()
+                .filter_map(|line| line.parse().ok())
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Return: `Ignores`
+    pub fn load() -> Self {
+        Self::parse(&storage::read_lines(IGNORES_FILE).unwrap_or_default())
+    }
+
+    /// Return: `io::Result<()>`
+    pub fn save(&self) -> io::Result<()> {
+        let lines: Vec<String> = self.rules.iter().map(Ignore::to_string).collect();
+        storage::write_lines(IGNORES_FILE, &lines)
+    }
+
+    /// Input: `Ignore`
+    pub fn add(&mut self, rule: Ignore) { self.rules.push(rule); }
+
+    /// Remove a Rule by its number, counting from one, as listed.
+    ///
+    /// Input: `usize`
+    /// Return: `Option<Ignore>`
+    pub fn remove(&mut self, number: usize) -> Option<Ignore> {
+        if 0 < number && number <= self.rules.len() {
+            Some(self.rules.remove(number - 1))
+        } else { None }
+    }
+
+    /// Return: `&[Ignore]`
+    pub fn rules(&self) -> &[Ignore] { &self.rules }
+
+    /// Return: `usize`
+    pub fn count(&self) -> usize { self.count }
+
+    /// Iterate over the most recent hidden Messages, oldest first.
+    ///
+    /// Return: `impl Iterator<Item = &Hidden>`
+    pub fn hidden(&self) -> impl Iterator<Item = &Hidden> { self.hidden.iter() }
+
+    /// Check whether a Message in a Channel should be hidden. If it should, it
+    ///     is counted and kept.
+    ///
+    /// Input: `&str`, `&Message`, `&str`
+    /// Return: `bool`
+    pub fn check(&mut self, channel: &str, msg: &Message, text: &str) -> bool {
+        let hide = self.rules.iter().any(|rule| {
+            rule.channel.as_ref().is_none_or(|ch| ch.eq_ignore_ascii_case(channel))
+                && rule.matcher.matches(msg, text)
+        });
+
+        if hide {
+            self.count += 1;
+
+            if self.hidden.len() >= HIDDEN_LIMIT { self.hidden.pop_front(); }
+            self.hidden.push_back(Hidden {
+                time: msg.time(),
+                channel: channel.to_owned(),
+                author: msg.author().to_owned(),
+                text: text.to_owned(),
+            });
+        }
+
+        hide
+    }
+}
+
+
+safe_static! {
+    pub static lazy IGNORES: RwLock<Ignores> = RwLock::new(Ignores::load());
+}
+
+
+#[cfg(test)]
+mod tests_filters {
+    use super::*;
+
+    fn sample(tags: &str, text: &str) -> Message {
+        format!(
+            "@{} :asdfqwert!asdfqwert@asdfqwert.tmi.twitch.tv PRIVMSG #zxcv :{}",
+            tags, text,
+        ).parse().expect("Failed to parse sample.")
+    }
+
+    /// Test to confirm that each kind of Matcher tests the right property.
+    #[test]
+    fn test_matchers() {
+        let msg = sample("badges=moderator/1,subscriber/12;bits=500", "Hello World");
+        let matches = |kind: &str, value: &str| {
+            Matcher::new(kind, value).expect("Failed to build Matcher.")
+                .matches(&msg, "Hello World")
+        };
+
+        assert!(matches("text", "hello w"), "Text not matched in any case.");
+        assert!(!matches("text", "goodbye"));
+        assert!(matches("regex", r"^Hel+o\b"), "Regex not matched.");
+        assert!(matches("author", "@AsdfQwert"), "Author not matched.");
+        assert!(matches("badge", "moderator"), "Badge not matched.");
+        assert!(!matches("badge", "mod"), "Partial Badge matched.");
+        assert!(matches("bits", "500"), "Bits not matched.");
+        assert!(!matches("bits", "501"), "Too few Bits matched.");
+
+        assert!(!matches("nobadge", "subscriber"), "Missing Badge matched.");
+        assert!(!matches("nobadge", "vip"), "Moderator matched by missing Badge.");
+        assert!(!matches("first", ""), "First Message matched without Tag.");
+        assert!(!matches("links", ""), "Link matched without a link.");
+
+        let sub = sample("badges=subscriber/12", "");
+        assert!(Matcher::new("nobadge", "vip").expect("Failed to build Matcher.")
+            .matches(&sub, ""), "Missing Badge not matched.");
+
+        let first = sample("first-msg=1;user-id=123", "see example.com/page");
+        assert!(Matcher::FirstMsg.matches(&first, ""), "First Message not matched.");
+        assert!(Matcher::new("id", "123").expect("Failed to build Matcher.")
+            .matches(&first, ""), "User ID not matched.");
+        assert!(Matcher::new("id", "asdf").is_err(), "Invalid User ID accepted.");
+
+        assert!(Matcher::new("regex", "(").is_err(), "Invalid Regex accepted.");
+        assert!(Matcher::new("color", "red").is_err(), "Unknown kind accepted.");
+    }
+
+    /// Test to confirm that links are found, without matching ordinary words.
+    #[test]
+    fn test_links() {
+        assert!(has_link("go to https://twitch.tv now"));
+        assert!(has_link("www.example"));
+        assert!(has_link("example.com/path?q=1"));
+        assert!(!has_link("e.g. this"), "Abbreviation matched as a link.");
+        assert!(!has_link("version 1.2"), "Number matched as a link.");
+        assert!(!has_link("the end."), "Sentence matched as a link.");
+        assert!(has_link("clips.twitch.tv/abc"), "Domain without scheme not matched.");
+        assert!(!has_link("lol.jk"), "Unknown domain matched as a link.");
+        assert!(!has_link("ok.thanks"), "Words matched as a link.");
+        assert!(!has_link("open file.txt"), "File name matched as a link.");
+    }
+
+    /// Test to confirm that Ignore Rules hide Messages, and keep only the most
+    ///     recent of them.
+    #[test]
+    fn test_ignores() {
+        let mut ignores = Ignores::parse(&[
+            String::from("#zxcv links"),
+            String::from("* author asdfqwert"),
+        ]);
+        let msg = sample("user-id=123", "hello");
+
+        assert_eq!("#zxcv links", ignores.rules()[0].to_string());
+        assert!(ignores.check("#ZXCV", &msg, "hello"), "Author not hidden.");
+        ignores.remove(2);
+        assert!(!ignores.check("#zxcv", &msg, "hello"), "Removed Rule still applied.");
+        assert!(!ignores.check("#other", &msg, "twitch.tv"), "Rule applied in wrong Channel.");
+
+        for _ in 0..HIDDEN_LIMIT { ignores.check("#zxcv", &msg, "twitch.tv"); }
+
+        assert_eq!(HIDDEN_LIMIT + 1, ignores.count(), "Hidden Messages counted wrong.");
+        assert_eq!(HIDDEN_LIMIT, ignores.hidden().count(), "Too many Messages kept.");
+        assert_eq!("twitch.tv", ignores.hidden().next().unwrap().text, "Oldest not dropped.");
+    }
+
+    /// Test to confirm that Rules survive being written to the file and read
+    ///     back, and only apply in their Channel.
+    #[test]
+    fn test_rules() {
+        let lines = vec![
+            String::from("#zxcv 1 copy text some phrase"),
+            String::from("not a rule"),
+            String::from("* - - badge vip"),
+            String::from("* - - bits lots"),
+            String::from("* 2 - first"),
+        ];
+        let mut highlights = Highlights::parse(&lines);
+
+        assert_eq!(3, highlights.rules().len(), "Invalid Rule loaded.");
+        assert_eq!(lines[4], highlights.rules()[2].to_string());
+        assert_eq!(lines[0], highlights.rules()[0].to_string());
+        assert_eq!(lines[2], highlights.rules()[1].to_string());
+
+        let msg = sample("badges=vip/1", "with some phrase");
+        let rule = highlights.find("#ZXCV", &msg, "with some phrase")
+            .expect("Rule not matched.");
+        assert!(rule.copy && rule.color == Some(1), "Wrong Rule matched.");
+        assert_eq!(None, highlights.find("#other", &msg, "with some phrase")
+            .and_then(|rule| rule.channel.clone()), "Rule matched in wrong Channel.");
+
+        assert!(highlights.remove(1).is_some());
+        assert!(highlights.remove(3).is_none(), "Removed a missing Rule.");
+        assert_eq!("badge", highlights.rules()[0].matcher.kind());
+    }
+}
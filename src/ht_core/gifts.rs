@@ -0,0 +1,176 @@
+//! Module for the aggregation of Gift Bombs. A `submysterygift` UserNotice is
+//!     followed by one `subgift` UserNotice for every Subscription given out,
+//!     all sharing the `msg-param-origin-id` of the original. These are folded
+//!     into one summary line, listing the recipients, once they stop arriving.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+
+/// Time to wait after the last Gift of a Bomb before its summary is printed.
+pub const DEBOUNCE: Duration = Duration::from_secs(2);
+
+
+/// Bomb: One Gift Bomb, with the recipients of its Gifts so far.
+#[derive(Debug)]
+pub struct Bomb {
+    pub channel: String,
+    pub sender: String,
+    pub count: usize,
+    pub recipients: Vec<String>,
+    last: Instant,
+}
+
+impl Bomb {
+    /// Check whether every Gift announced by the Bomb has arrived.
+    ///
+    /// Return: `bool`
+    pub fn is_complete(&self) -> bool { self.recipients.len() >= self.count }
+}
+
+
+/// Key of a Bomb: Channel, sender, and `msg-param-origin-id`, if there was one.
+type Key = (String, String, Option<String>);
+
+
+/// Bombs: All Gift Bombs still receiving their Gifts, in every Channel.
+#[derive(Default)]
+pub struct Bombs { inner: HashMap<Key, Bomb> }
+
+impl Bombs {
+    /// Record the start of a Gift Bomb, announced by a `submysterygift`.
+    ///
+    /// Input: `&str`, `&str`, `Option<String>`, `usize`, `Instant`
+    pub fn open(
+        &mut self,
+        channel: &str,
+        sender: &str,
+        origin: Option<String>,
+        count: usize,
+        now: Instant,
+    ) {
+        let channel = channel.to_ascii_lowercase();
+        let sender = sender.to_ascii_lowercase();
+
+        self.inner.insert((channel.clone(), sender.clone(), origin), Bomb {
+            channel,
+            sender,
+            count,
+            recipients: Vec::with_capacity(count),
+            last: now,
+        });
+    }
+
+    /// Add a Gift to the Bomb it belongs to. A Gift belongs to a Bomb if it has
+    ///     the same sender and origin ID; If the Gift has no origin ID, it goes
+    ///     to any Bomb from its sender that is still waiting for Gifts. Returns
+    ///     `false` if there is no such Bomb, in which case the Gift stands
+    ///     on its own.
+    ///
+    /// Input: `&str`, `&str`, `Option<&str>`, `&str`, `Instant`
+    /// Return: `bool`
+    pub fn gift(
+        &mut self,
+        channel: &str,
+        sender: &str,
+        origin: Option<&str>,
+        recipient: &str,
+        now: Instant,
+    ) -> bool {
+        let channel = channel.to_ascii_lowercase();
+        let sender = sender.to_ascii_lowercase();
+
+        let found = self.inner.iter_mut().find(|((ch, from, id), bomb)| {
+            *ch == channel && *from == sender && match origin {
+                Some(origin) => id.as_deref() == Some(origin),
+                None => !bomb.is_complete(),
+            }
+        });
+
+        match found {
+            Some((_, bomb)) if !bomb.is_complete() => {
+                bomb.recipients.push(recipient.to_owned());
+                bomb.last = now;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Take every Bomb which has received all of its Gifts, or has received no
+    ///     new Gift within the debounce window.
+    ///
+    /// Input: `Duration`, `Instant`
+    /// Return: `Vec<Bomb>`
+    pub fn flush(&mut self, debounce: Duration, now: Instant) -> Vec<Bomb> {
+        let done: Vec<Key> = self.inner.iter()
+            .filter(|(_, bomb)| {
+                bomb.is_complete() || now.duration_since(bomb.last) >= debounce
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        done.into_iter().filter_map(|key| self.inner.remove(&key)).collect()
+    }
+}
+
+
+safe_static! {
+    pub static lazy BOMBS: Mutex<Bombs> = Default::default();
+}
+
+
+#[cfg(test)]
+mod tests_gifts {
+    use super::*;
+
+    /// Test to confirm that Gifts are grouped by origin ID and sender.
+    #[test]
+    fn test_grouping() {
+        let mut bombs = Bombs::default();
+        let t0 = Instant::now();
+
+        bombs.open("#zxcv", "AsdfQwert", Some(String::from("abc")), 2, t0);
+
+        assert!(bombs.gift("#zxcv", "asdfqwert", Some("abc"), "one", t0),
+                "Gift with matching origin not absorbed.");
+        assert!(!bombs.gift("#zxcv", "asdfqwert", Some("xyz"), "two", t0),
+                "Gift with other origin absorbed.");
+        assert!(!bombs.gift("#zxcv", "someone", Some("abc"), "two", t0),
+                "Gift from other sender absorbed.");
+        assert!(!bombs.gift("#other", "asdfqwert", Some("abc"), "two", t0),
+                "Gift in other Channel absorbed.");
+        assert!(bombs.gift("#zxcv", "asdfqwert", None, "two", t0),
+                "Gift without origin not absorbed.");
+        assert!(!bombs.gift("#zxcv", "asdfqwert", Some("abc"), "three", t0),
+                "Gift absorbed beyond the count of its Bomb.");
+
+        let done = bombs.flush(DEBOUNCE, t0);
+
+        assert_eq!(1, done.len(), "Complete Bomb not flushed.");
+        assert_eq!(vec!["one", "two"], done[0].recipients);
+    }
+
+    /// Test to confirm that an incomplete Bomb is flushed only after the
+    ///     debounce window passes without a new Gift.
+    #[test]
+    fn test_debounce() {
+        let mut bombs = Bombs::default();
+        let t0 = Instant::now();
+        let t1 = t0 + DEBOUNCE / 2;
+
+        bombs.open("#zxcv", "asdfqwert", None, 5, t0);
+        bombs.gift("#zxcv", "asdfqwert", None, "one", t1);
+
+        assert!(bombs.flush(DEBOUNCE, t0 + DEBOUNCE).is_empty(),
+                "Bomb flushed within the debounce window.");
+        assert_eq!(1, bombs.flush(DEBOUNCE, t1 + DEBOUNCE).len(),
+                   "Bomb not flushed after the debounce window.");
+        assert!(!bombs.gift("#zxcv", "asdfqwert", None, "two", t1),
+                "Gift absorbed by a flushed Bomb.");
+    }
+}
@@ -0,0 +1,732 @@
+//! Module for requests to the Helix API of Twitch. The base URL of the API is
+//!     configurable, so that a local server can stand in for Twitch. The Client
+//!     ID and Token are kept in a file in the Plugin directory, in lines of the
+//!     form `key = value`. Older versions kept them as Preferences, which are
+//!     moved into the file when it is first loaded.
+
+use std::{
+    fmt,
+    io,
+    sync::mpsc::{channel, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use chrono::Utc;
+use hexchat::{delete_pref, get_pref_string};
+use parking_lot::Mutex;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use super::storage;
+
+
+/// Name of the file, in the Plugin directory, which keeps the settings.
+pub const CONFIG_FILE: &str = "helix.conf";
+/// The base URL of the real Helix API.
+pub const URL_DEFAULT: &str = "https://api.twitch.tv/helix";
+/// Time to wait for a response before giving up.
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// Most pages of a list which are requested, in case the API never stops
+///     returning a cursor.
+const PAGES_MAX: usize = 50;
+
+
+/// HelixError: A failure to complete a request.
+#[derive(Debug)]
+pub enum HelixError {
+    /// The Client ID or Token is not set.
+    Config(&'static str),
+    /// The rate limit is used up, for this many more seconds.
+    RateLimited(i64),
+    /// The API answered with an error status.
+    Status(u16, String),
+    /// The API could not be reached.
+    Transport(String),
+    /// The API answered with something that could not be understood.
+    Decode(String),
+}
+
+impl fmt::Display for HelixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Config(missing) => write!(f, "No {} is set.", missing),
+            Self::RateLimited(secs) => write!(f, "Rate limited for {} more seconds.", secs),
+            Self::Status(code, body) => write!(f, "Error {}: {}", code, body),
+            Self::Transport(e) => write!(f, "Request failed: {}", e),
+            Self::Decode(e) => write!(f, "Invalid response: {}", e),
+        }
+    }
+}
+
+
+/// Config: The settings for the API, as kept in the settings file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Config {
+    pub url: Option<String>,
+    pub client_id: Option<String>,
+    pub token: Option<String>,
+}
+
+impl Config {
+    /// The keys of the settings, as used in the settings file.
+    pub const KEYS: &'static [&'static str] = &["url", "client", "token"];
+
+    /// Input: `&str`
+    /// Return: `Option<&str>`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        match key {
+            "url" => self.url.as_deref(),
+            "client" => self.client_id.as_deref(),
+            "token" => self.token.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Change a setting, or remove it if the value is `None`. Returns `false`
+    ///     if the key is not known.
+    ///
+    /// Input: `&str`, `Option<String>`
+    /// Return: `bool`
+    pub fn set(&mut self, key: &str, value: Option<String>) -> bool {
+        let value = value.filter(|v| !v.is_empty());
+
+        match key {
+            "url" => self.url = value,
+            "client" => self.client_id = value,
+            "token" => self.token = value,
+            _ => return false,
+        }
+
+        true
+    }
+
+    /// Read settings from lines of the form `key = value`.
+    ///
+    /// Input: `&[String]`
+    /// Return: `Config`
+    pub fn parse(lines: &[String]) -> Self {
+        let mut config = Self::default();
+
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            if let Some(idx) = line.find('=') {
+                config.set(line[..idx].trim(), Some(line[idx + 1..].trim().to_owned()));
+            }
+        }
+
+        config
+    }
+
+    /// Write settings into lines of the form `key = value`.
+    ///
+    /// Return: `Vec<String>`
+    pub fn lines(&self) -> Vec<String> {
+        Self::KEYS.iter()
+            .filter_map(|&key| Some(format!("{} = {}", key, self.get(key)?)))
+            .collect()
+    }
+
+    /// Return: `io::Result<Config>`
+    pub fn load() -> io::Result<Self> {
+        //  Files saved by older versions may still be readable by others.
+        storage::restrict(CONFIG_FILE)?;
+        Ok(Self::parse(&storage::read_lines(CONFIG_FILE)?))
+    }
+
+    /// Load the settings, moving any which older versions kept as Preferences
+    ///     into the file, and deleting the Preferences, so that the Token is
+    ///     not left in the config of HexChat. Settings already in the file are
+    ///     kept over those in Preferences.
+    ///
+    /// Return: `io::Result<Config>`
+    pub fn load_migrated() -> io::Result<Self> {
+        let mut config = Self::load()?;
+        let mut moved = false;
+
+        for &key in Self::KEYS {
+            let pref = format!("PREF_helix_{}", key);
+
+            if let Some(value) = get_pref_string(&pref) {
+                if config.get(key).is_none() { config.set(key, Some(value)); }
+                delete_pref(&pref).ok();
+                moved = true;
+            }
+        }
+
+        if moved { config.save()?; }
+        Ok(config)
+    }
+
+    /// Return: `io::Result<()>`
+    pub fn save(&self) -> io::Result<()> {
+        storage::write_private(CONFIG_FILE, &self.lines())
+    }
+}
+
+
+/// Page: The body of a response, with the objects requested. Longer lists are
+///     split into Pages, each with a cursor pointing to the next.
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    data: Vec<T>,
+    #[serde(default)]
+    pagination: Pagination,
+}
+
+
+/// Pagination: The position of a Page within a longer list.
+#[derive(Debug, Default, Deserialize)]
+struct Pagination {
+    #[serde(default)]
+    cursor: Option<String>,
+}
+
+
+/// User: An Account on Twitch.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct User {
+    pub id: String,
+    pub login: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub broadcaster_type: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub created_at: String,
+}
+
+
+/// Stream: A Channel which is live.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Stream {
+    pub user_id: String,
+    pub user_login: String,
+    pub user_name: String,
+    #[serde(default)]
+    pub game_name: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub viewer_count: u64,
+    #[serde(default)]
+    pub started_at: String,
+}
+
+
+/// Channel: The information of a Channel, whether or not it is live.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Channel {
+    pub broadcaster_id: String,
+    pub broadcaster_login: String,
+    pub broadcaster_name: String,
+    #[serde(default)]
+    pub game_name: String,
+    #[serde(default)]
+    pub title: String,
+}
+
+
+/// Chatter: A User connected to the chat of a Channel.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Chatter {
+    pub user_id: String,
+    pub user_login: String,
+    pub user_name: String,
+}
+
+
+/// Reply: The end of a request made on a worker thread, to be run on the main
+///     thread of HexChat, where it may print and send Commands.
+pub type Reply = Box<dyn FnOnce() + Send>;
+
+
+/// RateLimit: The state of the rate limit, as last reported by the API.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct RateLimit {
+    remaining: u64,
+    /// The UNIX time, in seconds, at which the limit is refilled.
+    reset: i64,
+}
+
+
+/// HelixBackend: The requests to the API which commands depend on. `Helix` sends
+///     them to Twitch, but anything else which answers them can stand in.
+pub trait HelixBackend: Send {
+    /// Input: `&[&str]`
+    /// Return: `Result<Vec<User>, HelixError>`
+    fn users(&mut self, logins: &[&str]) -> Result<Vec<User>, HelixError>;
+
+    /// Input: `&[&str]`
+    /// Return: `Result<Vec<Stream>, HelixError>`
+    fn streams(&mut self, logins: &[&str]) -> Result<Vec<Stream>, HelixError>;
+
+    /// Input: `&[&str]`
+    /// Return: `Result<Vec<Channel>, HelixError>`
+    fn channels(&mut self, ids: &[&str]) -> Result<Vec<Channel>, HelixError>;
+
+    /// Input: `&str`, `&str`
+    /// Return: `Result<(), HelixError>`
+    fn modify_channel(&mut self, broadcaster_id: &str, title: &str) -> Result<(), HelixError>;
+}
+
+
+/// Helix: A client for the API, with its settings and rate limit.
+#[derive(Clone)]
+pub struct Helix {
+    pub config: Config,
+    limit: Option<RateLimit>,
+    /// The ID of the User who owns the Token, with the Token it was found for.
+    own: Option<(String, String)>,
+}
+
+impl Helix {
+    /// Input: `Config`
+    /// Return: `Helix`
+    pub fn new(config: Config) -> Self { Self { config, limit: None, own: None } }
+
+    /// Fail if the rate limit has been used up, and has not been refilled.
+    ///
+    /// Input: `i64`
+    /// Return: `Result<(), HelixError>`
+    fn check_limit(&self, now: i64) -> Result<(), HelixError> {
+        match self.limit {
+            Some(RateLimit { remaining: 0, reset }) if reset > now => {
+                Err(HelixError::RateLimited(reset - now))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Record the rate limit reported in the headers of a response.
+    ///
+    /// Input: `&ureq::Response`
+    fn update_limit(&mut self, resp: &ureq::Response) {
+        let header = |name| resp.header(name).and_then(|v| v.trim().parse().ok());
+
+        if let (Some(remaining), Some(reset)) = (
+            header("Ratelimit-Remaining"),
+            header("Ratelimit-Reset"),
+        ) {
+            self.limit = Some(RateLimit { remaining: remaining as u64, reset });
+        }
+    }
+
+    /// Send a request to an endpoint of the API, with authentication.
+    ///
+    /// Input: `&str`, `&str`, `&[(&str, &str)]`, `Option<Value>`
+    /// Return: `Result<ureq::Response, HelixError>`
+    fn send(
+        &mut self,
+        method: &str,
+        endpoint: &str,
+        query: &[(&str, &str)],
+        body: Option<Value>,
+    ) -> Result<ureq::Response, HelixError> {
+        self.check_limit(Utc::now().timestamp())?;
+
+        let client = self.config.client_id.as_deref()
+            .ok_or(HelixError::Config("Client ID"))?;
+        let token = self.config.token.as_deref()
+            .ok_or(HelixError::Config("Token"))?;
+        let base = self.config.url.as_deref().unwrap_or(URL_DEFAULT);
+
+        let mut req = ureq::request(
+            method,
+            &format!("{}/{}", base.trim_end_matches('/'), endpoint),
+        )
+            .timeout(TIMEOUT)
+            .set("Client-Id", client)
+            .set("Authorization", &format!("Bearer {}", token.trim_start_matches("oauth:")));
+
+        for (key, value) in query { req = req.query(key, value); }
+
+        let result = match body {
+            Some(json) => req.send_json(json),
+            None => req.call(),
+        };
+
+        match result {
+            Ok(resp) => {
+                self.update_limit(&resp);
+                Ok(resp)
+            }
+            Err(ureq::Error::Status(code, resp)) => {
+                self.update_limit(&resp);
+                Err(HelixError::Status(code, resp.into_string().unwrap_or_default()))
+            }
+            Err(ureq::Error::Transport(t)) => Err(HelixError::Transport(t.to_string())),
+        }
+    }
+
+    /// Request a list of objects from an endpoint of the API.
+    ///
+    /// Input: `&str`, `&[(&str, &str)]`
+    /// Return: `Result<Vec<T>, HelixError>`
+    fn get<T: DeserializeOwned>(
+        &mut self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>, HelixError> {
+        self.page(endpoint, query).map(|page| page.data)
+    }
+
+    /// Request one Page of a list from an endpoint of the API.
+    ///
+    /// Input: `&str`, `&[(&str, &str)]`
+    /// Return: `Result<Page<T>, HelixError>`
+    fn page<T: DeserializeOwned>(
+        &mut self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Page<T>, HelixError> {
+        self.send("GET", endpoint, query, None)?
+            .into_json::<Page<T>>()
+            .map_err(|e| HelixError::Decode(e.to_string()))
+    }
+
+    /// Request a whole list from an endpoint of the API, following the cursor
+    ///     of each Page to the next.
+    ///
+    /// Input: `&str`, `&[(&str, &str)]`
+    /// Return: `Result<Vec<T>, HelixError>`
+    fn get_all<T: DeserializeOwned>(
+        &mut self,
+        endpoint: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>, HelixError> {
+        let mut all: Vec<T> = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        for _ in 0..PAGES_MAX {
+            let mut query: Vec<(&str, &str)> = query.to_vec();
+            if let Some(after) = &cursor { query.push(("after", after)); }
+
+            let page = self.page::<T>(endpoint, &query)?;
+            all.extend(page.data);
+
+            match page.pagination.cursor.filter(|c| !c.is_empty()) {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(all)
+    }
+
+    /// Look up Users by their login names. With no names, this is the User who
+    ///     owns the Token.
+    ///
+    /// Input: `&[&str]`
+    /// Return: `Result<Vec<User>, HelixError>`
+    pub fn users(&mut self, logins: &[&str]) -> Result<Vec<User>, HelixError> {
+        let query: Vec<(&str, &str)> = logins.iter().map(|&l| ("login", l)).collect();
+        self.get("users", &query)
+    }
+
+    /// Look up the Streams of Channels by their login names. Channels which are
+    ///     not live are left out.
+    ///
+    /// Input: `&[&str]`
+    /// Return: `Result<Vec<Stream>, HelixError>`
+    pub fn streams(&mut self, logins: &[&str]) -> Result<Vec<Stream>, HelixError> {
+        let query: Vec<(&str, &str)> = logins.iter().map(|&l| ("user_login", l)).collect();
+        self.get("streams", &query)
+    }
+
+    /// Look up Channels by the IDs of their Broadcasters.
+    ///
+    /// Input: `&[&str]`
+    /// Return: `Result<Vec<Channel>, HelixError>`
+    pub fn channels(&mut self, ids: &[&str]) -> Result<Vec<Channel>, HelixError> {
+        let query: Vec<(&str, &str)> = ids.iter().map(|&id| ("broadcaster_id", id)).collect();
+        self.get("channels", &query)
+    }
+
+    /// List the Users in the chat of a Channel. This needs the ID of a User who
+    ///     moderates the Channel, and whose Token is used.
+    ///
+    /// Input: `&str`, `&str`
+    /// Return: `Result<Vec<Chatter>, HelixError>`
+    pub fn chatters(
+        &mut self,
+        broadcaster_id: &str,
+        moderator_id: &str,
+    ) -> Result<Vec<Chatter>, HelixError> {
+        self.get_all("chat/chatters", &[
+            ("broadcaster_id", broadcaster_id),
+            ("moderator_id", moderator_id),
+            ("first", "1000"),
+        ])
+    }
+
+    /// Find the ID of the User who owns the Token. It is only requested once
+    ///     for each Token.
+    ///
+    /// Return: `Result<String, HelixError>`
+    pub fn own_id(&mut self) -> Result<String, HelixError> {
+        let token = self.config.token.clone().unwrap_or_default();
+
+        if let Some((known, id)) = &self.own {
+            if *known == token { return Ok(id.clone()); }
+        }
+
+        match self.users(&[])?.pop() {
+            Some(user) => {
+                self.own = Some((token, user.id.clone()));
+                Ok(user.id)
+            }
+            None => Err(HelixError::Decode(String::from("No User for Token."))),
+        }
+    }
+
+    /// Set the Title of a Channel, by the ID of its Broadcaster.
+    ///
+    /// Input: `&str`, `&str`
+    /// Return: `Result<(), HelixError>`
+    pub fn modify_channel(&mut self, broadcaster_id: &str, title: &str) -> Result<(), HelixError> {
+        self.send(
+            "PATCH",
+            "channels",
+            &[("broadcaster_id", broadcaster_id)],
+            Some(json!({ "title": title })),
+        )?;
+
+        Ok(())
+    }
+}
+
+
+impl HelixBackend for Helix {
+    fn users(&mut self, logins: &[&str]) -> Result<Vec<User>, HelixError> {
+        Helix::users(self, logins)
+    }
+
+    fn streams(&mut self, logins: &[&str]) -> Result<Vec<Stream>, HelixError> {
+        Helix::streams(self, logins)
+    }
+
+    fn channels(&mut self, ids: &[&str]) -> Result<Vec<Channel>, HelixError> {
+        Helix::channels(self, ids)
+    }
+
+    fn modify_channel(&mut self, broadcaster_id: &str, title: &str) -> Result<(), HelixError> {
+        Helix::modify_channel(self, broadcaster_id, title)
+    }
+}
+
+
+safe_static! {
+    pub static lazy HELIX: Mutex<Helix> = Mutex::new(Helix::new(
+        Config::load_migrated().unwrap_or_default(),
+    ));
+}
+
+
+// Replies sent back by worker threads, waiting to be run by a Timer.
+safe_static! {
+    static lazy REPLIES: Mutex<(Sender<Reply>, Receiver<Reply>)> = Mutex::new(channel());
+}
+
+
+/// Make requests to the API on a worker thread, so that HexChat does not wait
+///     for them. The job works on a copy of the client, so the lock on `HELIX`
+///     is never held during a request; the rate limit, and the ID of the User,
+///     are copied back after.
+///     The Reply returned by the job is run later, by `collect()`.
+///
+/// Input: `FnOnce(&mut Helix) -> Reply`
+pub fn spawn(job: impl FnOnce(&mut Helix) -> Reply + Send + 'static) {
+    let mut api = HELIX.lock().clone();
+    let replies = REPLIES.lock().0.clone();
+
+    thread::spawn(move || {
+        let reply = job(&mut api);
+
+        {
+            let mut helix = HELIX.lock();
+            helix.limit = api.limit;
+            if api.own.is_some() { helix.own = api.own.take(); }
+        }
+        replies.send(reply).ok();
+    });
+}
+
+
+/// Take the Replies of all jobs which have finished. These must be run on the
+///     main thread.
+///
+/// Return: `Vec<Reply>`
+pub fn collect() -> Vec<Reply> {
+    REPLIES.lock().1.try_iter().collect()
+}
+
+
+#[cfg(test)]
+mod tests_helix {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    /// Serve requests on a local port, answering each with the next of a list
+    ///     of responses, as a status line, extra headers, and a body. Returns
+    ///     the base URL, and a handle which joins with the requests that were
+    ///     received, as their heads and bodies.
+    fn mock(
+        responses: &'static [(&str, &str, &str)],
+    ) -> (String, thread::JoinHandle<Vec<(String, String)>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind mock.");
+        let base = format!("http://{}/helix", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || responses.iter().map(|(status, headers, body)| {
+            let (stream, _) = listener.accept().expect("Failed to accept request.");
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+            let mut length = 0;
+
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).expect("Failed to read request.");
+
+                if let Some(n) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = n.trim().parse().unwrap_or(0);
+                }
+                if line.trim().is_empty() { break; }
+                head.push_str(&line);
+            }
+
+            let mut content = vec![0; length];
+            reader.read_exact(&mut content).expect("Failed to read body.");
+
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status, headers, body.len(), body,
+            ).expect("Failed to respond.");
+
+            (head, String::from_utf8(content).unwrap())
+        }).collect());
+
+        (base, handle)
+    }
+
+    fn client(base: &str) -> Helix {
+        Helix::new(Config {
+            url: Some(base.to_owned()),
+            client_id: Some(String::from("client")),
+            token: Some(String::from("oauth:token")),
+        })
+    }
+
+    /// Test to confirm that a Title is sent as the API expects it.
+    #[test]
+    fn test_modify_channel() {
+        let (base, handle) = mock(&[("204 No Content", "", "")]);
+
+        client(&base).modify_channel("12345", "New Title").expect("Request failed.");
+
+        let requests = handle.join().unwrap();
+        let (head, body) = &requests[0];
+
+        assert!(head.starts_with("PATCH /helix/channels?broadcaster_id=12345 "),
+                "Wrong request line: {}", head);
+        assert!(head.contains("Client-Id: client"), "Client ID not sent.");
+        assert!(head.contains("Authorization: Bearer token"), "Token not sent.");
+        assert_eq!(r#"{"title":"New Title"}"#, body);
+    }
+
+    /// Test to confirm that responses are decoded into their types.
+    #[test]
+    fn test_users() {
+        let (base, handle) = mock(&[("200 OK", "", r#"{"data":[{
+            "id":"141981764","login":"twitchdev","display_name":"TwitchDev",
+            "type":"","broadcaster_type":"partner","description":"Hello",
+            "profile_image_url":"","view_count":0,"created_at":"2016-12-14T20:32:28Z"
+        }]}"#)]);
+
+        let users = client(&base).users(&["twitchdev", "asdf"]).expect("Request failed.");
+        let (head, _) = &handle.join().unwrap()[0];
+
+        assert!(head.starts_with("GET /helix/users?login=twitchdev&login=asdf "),
+                "Wrong request line: {}", head);
+        assert_eq!(1, users.len(), "Wrong number of Users.");
+        assert_eq!("TwitchDev", users[0].display_name);
+        assert_eq!("partner", users[0].broadcaster_type);
+    }
+
+    /// Test to confirm that every Page of a list is requested, and that the ID
+    ///     of the User is only requested once.
+    #[test]
+    fn test_chatters() {
+        let (base, handle) = mock(&[
+            ("200 OK", "", r#"{"data":[{"id":"9","login":"me","display_name":"Me"}]}"#),
+            ("200 OK", "", r#"{"data":[
+                {"user_id":"1","user_login":"a","user_name":"A"}
+            ],"pagination":{"cursor":"next"}}"#),
+            ("200 OK", "", r#"{"data":[
+                {"user_id":"2","user_login":"b","user_name":"B"}
+            ],"pagination":{}}"#),
+        ]);
+        let mut helix = client(&base);
+
+        assert_eq!("9", helix.own_id().expect("Request failed."));
+        assert_eq!("9", helix.own_id().expect("ID not kept."));
+
+        let chatters = helix.chatters("12345", "9").expect("Request failed.");
+        let requests = handle.join().unwrap();
+
+        assert_eq!(3, requests.len(), "Wrong number of requests.");
+        assert!(requests[2].0.contains("after=next"), "Cursor not followed: {}", requests[2].0);
+        assert_eq!(vec!["a", "b"], chatters.iter()
+            .map(|c| c.user_login.as_str())
+            .collect::<Vec<_>>(), "Wrong Chatters.");
+    }
+
+    /// Test to confirm that an error status is reported, with its message, and
+    ///     that a used up rate limit stops further requests until it resets.
+    #[test]
+    fn test_rate_limit() {
+        let (base, handle) = mock(&[(
+            "429 Too Many Requests",
+            "Ratelimit-Limit: 800\r\nRatelimit-Remaining: 0\r\nRatelimit-Reset: 99999999999\r\n",
+            "Too Many Requests",
+        )]);
+        let mut helix = client(&base);
+
+        match helix.streams(&["asdf"]) {
+            Err(HelixError::Status(429, body)) => assert_eq!("Too Many Requests", body),
+            other => panic!("Wrong result: {:?}", other),
+        }
+        handle.join().unwrap();
+
+        match helix.streams(&["asdf"]) {
+            Err(HelixError::RateLimited(secs)) => assert!(secs > 0, "Wrong wait."),
+            other => panic!("Request sent while rate limited: {:?}", other),
+        }
+
+        assert!(helix.check_limit(99999999999).is_ok(), "Rate limit did not reset.");
+    }
+
+    /// Test to confirm that settings are read back as they were written.
+    #[test]
+    fn test_config() {
+        let mut config = Config::default();
+
+        assert!(config.set("client", Some(String::from("abc"))));
+        assert!(config.set("token", Some(String::from("xyz"))));
+        assert!(!config.set("nothing", Some(String::from("?"))), "Unknown key set.");
+
+        let mut lines = config.lines();
+        lines.insert(0, String::from("# comment"));
+
+        assert_eq!(config, Config::parse(&lines), "Settings changed on reading.");
+        assert_eq!(None, Config::parse(&lines).url);
+    }
+}
@@ -6,6 +6,8 @@ use std::{
     ops::Try,
 };
 
+use chrono::{DateTime, TimeZone, Utc};
+
 
 /// Given a string which may contain characters which are not allowed in an IRC
 ///     Tag String, replace all such characters with escaped substitutions.
@@ -197,6 +199,24 @@ impl Message {
         Some(unescape(self.tags.as_ref()?.get(key)?))
     }
 
+    /// Get the time at which Twitch says this `Message` was sent, from the
+    ///     `tmi-sent-ts` Tag. This is a number of milliseconds since the Unix
+    ///     Epoch.
+    ///
+    /// Return: `Option<DateTime<Utc>>`
+    pub fn sent_time(&self) -> Option<DateTime<Utc>> {
+        let ms: i64 = self.get_tag("tmi-sent-ts")?.parse().ok()?;
+        Utc.timestamp_millis_opt(ms).single()
+    }
+
+    /// Get the time at which this `Message` was sent, or the current time if
+    ///     it is not known.
+    ///
+    /// Return: `DateTime<Utc>`
+    pub fn time(&self) -> DateTime<Utc> {
+        self.sent_time().unwrap_or_else(Utc::now)
+    }
+
     /// Set a Tag on the `Message`. If the Tag was already present, its old
     ///     value is returned. If the `Message` has `None` for its Tags field,
     ///     `Err(())` is returned.
@@ -320,6 +340,7 @@ mod tests_irc {
     extern crate test;
 
     use super::*;
+    use chrono::NaiveDate;
     use test::Bencher;
 
     const MSG_WITHOUT_TAGS: &str = r":asdfqwert!asdfqwert@asdfqwert.tmi.twitch.tv WHISPER thyself :asdf";
@@ -433,6 +454,27 @@ mod tests_irc {
         }
     }
 
+    /// Test to confirm that the `tmi-sent-ts` Tag is read as a time.
+    #[test]
+    fn test_sent_time() {
+        let msg: Message = SAMPLES[4].parse().expect("Failed to parse sample.");
+        assert_eq!(
+            NaiveDate::from_ymd_opt(2020, 2, 29)
+                .and_then(|date| date.and_hms_milli_opt(6, 45, 44, 397))
+                .map(|time| Utc.from_utc_datetime(&time)),
+            msg.sent_time(),
+            "Sent time is not read correctly.",
+        );
+
+        let tagless: Message = MSG_WITHOUT_TAGS.parse()
+            .expect("Failed to parse tagless sample.");
+        assert_eq!(
+            None,
+            tagless.sent_time(),
+            "Tagless Message returns a sent time.",
+        );
+    }
+
     /// Benchmark performance of `&str`s being parsed into `Message`s..
     #[bench]
     fn bench_samples_0tags(b: &mut Bencher) {
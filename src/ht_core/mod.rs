@@ -1,14 +1,30 @@
+mod bits;
+mod cheermotes;
 mod events;
+mod filters;
+mod gifts;
+mod helix;
 mod irc;
+mod notices;
+mod outgoing;
 mod output;
+mod profiles;
+mod raids;
+mod streams;
+mod storage;
+mod users;
+mod whispers;
 
 
-use chrono::{DateTime, Utc};
+use std::{collections::HashMap, time::{Duration, Instant}};
+
+use chrono::{DateTime, Local, Utc};
 use hexchat::{
     ChannelRef,
     delete_pref,
     EatMode,
     get_channel_name,
+    get_current_channel,
     get_network_name,
     get_pref_int,
     get_pref_string,
@@ -19,17 +35,28 @@ use hexchat::{
     set_pref_string,
     strip_formatting,
 };
-use parking_lot::Mutex;
+use parking_lot::{Mutex, RwLock};
 
 use irc::Message;
 use output::{
+    Badges,
+    Category,
     echo,
+    EVENT_ALERT,
     EVENT_ERR,
     EVENT_NORMAL,
+    in_channel,
+    is_quiet_topic,
     print_with_irc,
     print_without_irc,
+    set_topic,
     TABCOLORS,
 };
+use profiles::PROFILES;
+use raids::{RAIDERS_DEFAULT, RAIDS};
+use streams::{POLL_DEFAULT, STREAMS};
+use users::{IDLE_DEFAULT, USERS};
+use whispers::Route;
 
 
 #[derive(Default)]
@@ -67,6 +94,13 @@ safe_static! {
 }
 
 
+//  ROOM_IDS: The IDs of Channels, as received in their RoomStates, for
+//      requests to the API.
+safe_static! {
+    pub static lazy ROOM_IDS: RwLock<HashMap<String, String>> = Default::default();
+}
+
+
 fn check_message(channel: &str, author: &str) -> Option<Message> {
     let sig: &str = &format!(
         "{}:{}",
@@ -78,27 +112,263 @@ fn check_message(channel: &str, author: &str) -> Option<Message> {
 }
 
 
-/// Reset the Color of a newly-focused Tab.
-pub(crate) fn cb_focus(_channel: ChannelRef) -> EatMode {
+/// Forget the Color of a closed Tab.
+pub(crate) fn cb_close(_channel: ChannelRef) -> EatMode {
+    TABCOLORS.write().close();
+
     if get_network_name().unwrap_or_default().eq_ignore_ascii_case("twitch") {
-        TABCOLORS.write().reset();
+        let channel = get_channel_name();
+        ROOM_IDS.write().remove(&channel);
+        STREAMS.lock().forget(&channel);
+        USERS.write().clear(&channel);
+        outgoing::JOINS.lock().left(Some(&channel));
     }
+
+    EatMode::None
+}
+
+
+/// Reset the Color of a newly-focused Tab.
+pub(crate) fn cb_focus(_channel: ChannelRef) -> EatMode {
+    TABCOLORS.write().reset();
     EatMode::None
 }
 
 
-/// Hide a Join Event if it is fake.
+/// Hide a Join Event if it is fake. Real Joins are recorded, so that the User
+///     is not Joined again by the Plugin.
 pub(crate) fn cb_join(_etype: PrintEvent, word: &[String]) -> EatMode {
-    if get_network_name().unwrap_or_default().eq_ignore_ascii_case("twitch")
-        && !word[2].contains("tmi.twitch.tv")
-    {
-        EatMode::All
+    if get_network_name().unwrap_or_default().eq_ignore_ascii_case("twitch") {
+        if word[2].contains("tmi.twitch.tv") {
+            USERS.write().joined(&word[1], &word[0], Instant::now());
+            EatMode::None
+        } else {
+            EatMode::All
+        }
+    } else {
+        EatMode::None
+    }
+}
+
+
+/// Hide a Part Event if it is fake. Real Parts remove the User from the Users
+///     being tracked.
+pub(crate) fn cb_part(_etype: PrintEvent, word: &[String]) -> EatMode {
+    if get_network_name().unwrap_or_default().eq_ignore_ascii_case("twitch") {
+        if word[1].contains("tmi.twitch.tv") {
+            USERS.write().part(&word[2], &word[0]);
+            EatMode::None
+        } else {
+            EatMode::All
+        }
     } else {
         EatMode::None
     }
 }
 
 
+/// Forget the Users of a Channel when the User Joins, Parts, or is Kicked from
+///     it, and of every Channel when the connection is lost. In each case, the
+///     User List of HexChat starts over empty, so the Users must be Joined
+///     again by the Plugin. The Channels the User is in are tracked as well.
+pub(crate) fn cb_self(etype: PrintEvent, _word: &[String]) -> EatMode {
+    if get_network_name().unwrap_or_default().eq_ignore_ascii_case("twitch") {
+        let channel = get_channel_name();
+
+        if etype == PrintEvent::DISCONNECTED {
+            USERS.write().clear_all();
+            outgoing::JOINS.lock().left(None);
+        } else {
+            USERS.write().clear(&channel);
+
+            if etype == PrintEvent::YOU_JOIN {
+                outgoing::JOINS.lock().joined(&channel);
+            } else {
+                outgoing::JOINS.lock().left(Some(&channel));
+            }
+        }
+    }
+
+    EatMode::None
+}
+
+
+/// Add Users who have spoken recently to the User Lists of Channels, by sending
+///     fake Joins. Run frequently by a Timer, so that a busy Channel does not
+///     send a Command for every Message.
+pub(crate) fn cb_joins() {
+    let joins = USERS.write().flush();
+
+    for (channel, users) in joins {
+        let sent = in_channel(&channel, || for user in &users {
+            send_command(&format!(
+                "RECV :{0}!{0}@twitch.tv/{0} JOIN {1}",
+                user,
+                channel,
+            ));
+        });
+
+        //  The Tab is not open, so try again on the next run.
+        if !sent { USERS.write().restore(&channel, users); }
+    }
+}
+
+
+/// Send Joins and Messages which are waiting in the queues, as fast as the
+///     Twitch limits allow. Run frequently by a Timer.
+pub(crate) fn cb_outgoing() {
+    outgoing::flush();
+}
+
+
+/// Handle text typed into a Tab. In a Channel where it is enabled, a Message
+///     repeated too soon is changed, so that Twitch does not reject it.
+pub(crate) fn cb_input(arg: &[String]) -> EatMode {
+    let channel = get_channel_name();
+
+    if !channel.starts_with('#')
+        || !get_network_name().unwrap_or_default().eq_ignore_ascii_case("twitch")
+        || !outgoing::bypass_enabled(&channel)
+    {
+        return EatMode::None;
+    }
+
+    //  This hook is on plain input, so every word is part of the text, and
+    //      none of them is the name of a Command.
+    let changed = outgoing::DUPLICATES.lock().input(&channel, arg, Instant::now());
+
+    match changed {
+        Some(changed) => {
+            outgoing::send(&channel, format!("SAY {}", changed));
+            EatMode::All
+        }
+        None => EatMode::None,
+    }
+}
+
+
+/// Remove Users who have not spoken recently from the User Lists of Channels,
+///     by sending fake Parts. Run periodically by a Timer.
+pub(crate) fn cb_idle() {
+    let minutes = get_pref_int("PREF_idle_timeout")
+        .map_or(IDLE_DEFAULT, |m| m.max(0) as u64);
+
+    if minutes == 0 { return; }
+
+    let idle = USERS.write().expire(
+        Duration::from_secs(minutes * 60),
+        Instant::now(),
+    );
+
+    for (channel, user) in idle {
+        in_channel(&channel, || send_command(&format!(
+            "RECV :{0}!{0}@twitch.tv/{0} PART {1}",
+            user,
+            channel,
+        )));
+    }
+}
+
+
+/// Print the summaries of Gift Bombs which have finished. Run periodically by
+///     a Timer.
+pub(crate) fn cb_gifts() {
+    let bombs = gifts::BOMBS.lock().flush(gifts::DEBOUNCE, Instant::now());
+
+    for bomb in bombs {
+        let templates = notices::TEMPLATES.read();
+
+        if let Some(text) = notices::render_bomb(&bomb, &templates) {
+            in_channel(&bomb.channel, || echo(EVENT_ALERT, &[
+                templates.get("label.sub").unwrap_or_default(),
+                text.as_str(),
+            ], Category::Sub.color()));
+        }
+    }
+}
+
+
+/// Poll the API for the live status of all joined Channels, if it is time to,
+///     and update their topics. Run periodically by a Timer.
+pub(crate) fn cb_streams() {
+    let interval = get_pref_int("PREF_stream_poll")
+        .map_or(POLL_DEFAULT, |s| s.max(0) as u64);
+
+    if interval == 0 { return; }
+
+    {
+        let api = helix::HELIX.lock();
+        if api.config.client_id.is_none() || api.config.token.is_none() { return; }
+    }
+
+    let channels: Vec<(String, String)> = ROOM_IDS.read().iter()
+        .map(|(channel, id)| (channel.to_owned(), id.to_owned()))
+        .collect();
+
+    if channels.is_empty()
+        || !STREAMS.lock().due(Duration::from_secs(interval), Instant::now())
+    { return; }
+
+    helix::spawn(move |api| {
+        let result = streams::poll(api, &channels);
+        Box::new(move || streams_polled(result))
+    });
+}
+
+
+/// Update the topics of Channels with the results of a poll, and announce the
+///     Channels which went live or offline. Run on the main thread, after the
+///     poll has finished on a worker thread.
+///
+/// Input: `Result<Vec<(String, Status)>, HelixError>`
+fn streams_polled(result: Result<Vec<(String, streams::Status)>, helix::HelixError>) {
+    match result {
+        Ok(statuses) => {
+            STREAMS.lock().finish(true);
+
+            for (channel, status) in statuses {
+                let update = STREAMS.lock().update(&channel, status);
+
+                in_channel(&channel, || {
+                    if let Some(topic) = &update.topic { set_topic(&channel, topic); }
+
+                    match update.went_live {
+                        Some(true) => echo(EVENT_ALERT, &[
+                            "LIVE",
+                            &format!("{} is now live.", channel),
+                        ], Category::Stream.color()),
+                        Some(false) => echo(EVENT_ALERT, &[
+                            "OFFLINE",
+                            &format!("{} is now offline.", channel),
+                        ], Category::Stream.color()),
+                        None => {}
+                    }
+                });
+            }
+        }
+        Err(e) => if STREAMS.lock().finish(false) {
+            echo(EVENT_ERR, &[format!(
+                "FAILED to poll Streams: {} (Further failures will not be shown.)",
+                e,
+            )], 0);
+        },
+    }
+}
+
+
+/// Run the Replies of requests to the API which have finished. Run frequently
+///     by a Timer.
+pub(crate) fn cb_helix() {
+    for reply in helix::collect() { reply(); }
+}
+
+
+/// Hide the announcement of a topic change made by the Plugin.
+pub(crate) fn cb_topic(_etype: PrintEvent, _word: &[String]) -> EatMode {
+    if is_quiet_topic() { EatMode::All } else { EatMode::None }
+}
+
+
 pub(crate) fn cb_print(etype: PrintEvent, word: &[String]) -> EatMode {
     match get_network_name() {
         Some(network) if network.eq_ignore_ascii_case("twitch") => {
@@ -132,6 +402,7 @@ pub(crate) fn cb_server(_word: &[String], _dt: DateTime<Utc>, raw: String) -> Ea
                     Some(EatMode::None)
                 }
                 "WHISPER" => events::whisper_recv(msg),
+                "NOTICE" => events::notice(msg),
 
                 //  Status updates.
                 "HOSTTARGET" => events::hosttarget(msg),
@@ -161,17 +432,68 @@ pub(crate) fn cb_server(_word: &[String], _dt: DateTime<Utc>, raw: String) -> Ea
 }
 
 
-pub(crate) fn cmd_ht_debug(_arg: &[String]) -> EatMode {
-    let new: bool = get_pref_int("PREF_htdebug").unwrap_or(0) == 0;
+pub(crate) fn cmd_big_cheer(argslice: &[String]) -> EatMode {
+    match argslice.get(1).map(String::as_str).unwrap_or_default() {
+        "" => match bits::big_threshold() {
+            Some(n) => {
+                echo(EVENT_NORMAL, &[format!(
+                    "Cheers of {} Bits or more are alerted.",
+                    n,
+                )], 0);
+            }
+            None => { echo(EVENT_NORMAL, &["Big Cheers are not alerted."], 0); }
+        }
+        arg => match arg.parse::<i32>().ok().filter(|&n| n >= 0) {
+            Some(n) if set_pref_int("PREF_bits_alert", n).is_ok() => {
+                echo(EVENT_NORMAL, &["Preference set."], 0);
+            }
+            Some(_) => { echo(EVENT_ERR, &["FAILED to set Preference."], 0); }
+            None => { echo(EVENT_ERR, &["Usage: BIGCHEER [<bits>]"], 0); }
+        }
+    }
 
-    if set_pref_int("PREF_htdebug", new.into()).is_ok() {
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_bits(argslice: &[String]) -> EatMode {
+    let channel: String = match argslice.get(1).map(String::as_str).unwrap_or_default() {
+        "" => get_channel_name(),
+        ch if ch.starts_with('#') => ch.to_ascii_lowercase(),
+        ch => format!("#{}", ch.to_ascii_lowercase()),
+    };
+    let bits = bits::BITS.read();
+    let board = bits.leaderboard(&channel, 10);
+
+    if board.is_empty() {
+        echo(EVENT_NORMAL, &[format!("No Bits have been cheered in {}.", channel)], 0);
+    } else {
+        echo(EVENT_NORMAL, &[format!("BITS CHEERED IN {}:", channel)], 0);
+
+        for (rank, (user, total)) in board.iter().enumerate() {
+            echo(EVENT_NORMAL, &[format!("{:>2}. {}: {}", rank + 1, user, total)], 0);
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_bits_keep(_arg: &[String]) -> EatMode {
+    let new: bool = get_pref_int("PREF_bits_persist").unwrap_or(0) == 0;
+
+    if set_pref_int("PREF_bits_persist", new.into()).is_ok() {
         if new {
             echo(EVENT_NORMAL, &[
-                "Unrecognized UserNotices will now show the full Message.",
+                "Bits cheered will now be kept between sessions.",
             ], 0);
+
+            if let Err(e) = bits::restore() {
+                echo(EVENT_ERR, &[format!("Failed to read kept Bits: {}", e)], 0);
+            }
         } else {
             echo(EVENT_NORMAL, &[
-                "Unrecognized UserNotices will NOT show the full Message.",
+                "Bits cheered will now only be counted for this session.",
             ], 0);
         }
     } else {
@@ -182,45 +504,60 @@ pub(crate) fn cmd_ht_debug(_arg: &[String]) -> EatMode {
 }
 
 
-pub(crate) fn cmd_reward(argslice: &[String]) -> EatMode {
-    let arg: Vec<&str> = argslice[1..].iter()
-        .take_while(|s| !s.is_empty())
-        .map(String::as_str)
-        .collect();
-    let len = arg.len();
+pub(crate) fn cmd_chatters(_arg: &[String]) -> EatMode {
+    let channel: String = get_channel_name();
+    let room_id: String = match ROOM_IDS.read().get(&channel) {
+        Some(id) => id.to_owned(),
+        None => {
+            echo(EVENT_ERR, &[format!("The ID of {} is not known.", channel)], 0);
+            return EatMode::All;
+        }
+    };
 
-    if len < 1 {
-        //  Print the current Reward Names.
-        echo(EVENT_NORMAL, &["REWARD EVENTS:"], 0);
-        for pref in get_prefs() {
-            if !pref.is_empty() && !pref.starts_with("PREF") {
-                echo(
-                    EVENT_NORMAL,
-                    &[format!(
-                        "{}: '{}'",
-                        pref,
-                        get_pref_string(&pref)
-                            .unwrap_or_default(),
-                    )],
-                    0,
-                );
+    helix::spawn(move |api| {
+        let result = api.own_id().and_then(|own| api.chatters(&room_id, &own));
+
+        Box::new(move || match result {
+            Ok(chatters) => {
+                let now = Instant::now();
+                let mut users = USERS.write();
+
+                for chatter in &chatters { users.seen(&channel, &chatter.user_login, now); }
+
+                echo(EVENT_NORMAL, &[format!(
+                    "{} Users are in the chat of {}.",
+                    chatters.len(),
+                    channel,
+                )], 0);
             }
+            Err(e) => { echo(EVENT_ERR, &[format!("FAILED to list Chatters: {}", e)], 0); }
+        })
+    });
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_cheermotes(argslice: &[String]) -> EatMode {
+    let mode = match argslice.get(1).map(String::as_str).unwrap_or_default() {
+        "" => {
+            echo(EVENT_NORMAL, &[match cheermotes::Mode::current() {
+                cheermotes::Mode::Plain => "Cheermotes are shown unchanged.",
+                cheermotes::Mode::Compact => "Cheermotes are shown as their total Bits.",
+                cheermotes::Mode::Tiers => "Cheermotes are colored by their tier.",
+            }], 0);
+            return EatMode::All;
         }
-    } else if {
-        !arg[0].starts_with("PREF")
-            && {
-            if len < 2 {
-                //  Unset a Reward.
-                delete_pref(&arg[0].to_lowercase())
-            } else {
-                //  Set a Reward.
-                set_pref_string(
-                    &arg[0].to_lowercase(),
-                    &arg[1..].join(" ").trim(),
-                )
-            }
-        }.is_ok()
-    } {
+        arg if arg.eq_ignore_ascii_case("plain") => 0,
+        arg if arg.eq_ignore_ascii_case("compact") => 1,
+        arg if arg.eq_ignore_ascii_case("tiers") => 2,
+        _ => {
+            echo(EVENT_ERR, &["Usage: CHEERMOTES [plain|compact|tiers]"], 0);
+            return EatMode::All;
+        }
+    };
+
+    if set_pref_int("PREF_cheermotes", mode).is_ok() {
         echo(EVENT_NORMAL, &["Preference set."], 0);
     } else {
         echo(EVENT_ERR, &["FAILED to set Preference."], 0);
@@ -230,59 +567,89 @@ pub(crate) fn cmd_reward(argslice: &[String]) -> EatMode {
 }
 
 
-pub(crate) fn cmd_title(arg: &[String]) -> EatMode {
-    send_command(&format!(
-        "RECV :Twitch@twitch.tv TOPIC #{} :{}",
-        &arg[1].to_ascii_lowercase(),
-        &arg[2..].join(" ").trim(),
-    ));
+pub(crate) fn cmd_dupe_bypass(argslice: &[String]) -> EatMode {
+    let channel: String = match argslice.get(1).map(String::as_str).unwrap_or_default() {
+        "" => get_channel_name(),
+        "list" => {
+            echo(EVENT_NORMAL, &["DUPLICATE BYPASS ENABLED IN:"], 0);
+            let prefix = outgoing::bypass_pref("");
+            for pref in get_prefs() {
+                if let Some(channel) = pref.strip_prefix(&prefix) {
+                    if outgoing::bypass_enabled(channel) {
+                        echo(EVENT_NORMAL, &[channel], 0);
+                    }
+                }
+            }
+            return EatMode::All;
+        }
+        name => format!("#{}", name.trim_start_matches('#')),
+    }.to_ascii_lowercase();
 
-    EatMode::All
-}
+    if !channel.starts_with('#') {
+        echo(EVENT_ERR, &["Usage: DUPEBYPASS [<channel>|list]"], 0);
+        return EatMode::All;
+    }
 
+    let new = !outgoing::bypass_enabled(&channel);
+    let result = if new {
+        set_pref_int(&outgoing::bypass_pref(&channel), 1)
+    } else {
+        delete_pref(&outgoing::bypass_pref(&channel))
+    };
 
-pub(crate) fn cmd_tjoin(arg: &[String]) -> EatMode {
-    send_command(&format!(
-        "JOIN {}",
-        &arg[1..].join(" ").trim(),
-    ));
+    if result.is_ok() {
+        if new {
+            echo(EVENT_NORMAL, &[format!(
+                "Repeated Messages in {} will now be changed to get through.",
+                channel,
+            )], 0);
+        } else {
+            echo(EVENT_NORMAL, &[format!(
+                "Repeated Messages in {} will now be sent unchanged.",
+                channel,
+            )], 0);
+        }
+    } else {
+        echo(EVENT_ERR, &["FAILED to set Preference."], 0);
+    }
 
     EatMode::All
 }
 
 
-pub(crate) fn cmd_whisper(arg: &[String]) -> EatMode {
-    if arg.len() > 1
-        && get_network_name().unwrap_or_default()
-        .eq_ignore_ascii_case("twitch")
-    {
-        let targ: &str = &arg[1];
-
-        //  Two stage assignment to prevent Temporary Value.
-        let tmp: String = arg[2..].join(" ");
-        let msg: &str = tmp.trim();
+pub(crate) fn cmd_first_alert(_arg: &[String]) -> EatMode {
+    let new: bool = get_pref_int("PREF_first_alert").unwrap_or(0) == 0;
 
-        //  Check for trailing Arguments.
-        if msg.is_empty() {
-            //  None: Switch to Whisper Tab.
-            send_command(&format!("QUERY {}", targ));
+    if set_pref_int("PREF_first_alert", new.into()).is_ok() {
+        if new {
+            echo(EVENT_NORMAL, &[
+                "The first Message of a new chatter will now be announced.",
+            ], 0);
         } else {
-            //  Some: Send through Whisper.
-            send_command(&format!("SAY .w {} {}", targ, msg));
+            echo(EVENT_NORMAL, &[
+                "The first Message of a new chatter will now only be marked.",
+            ], 0);
         }
+    } else {
+        echo(EVENT_ERR, &["FAILED to set Preference."], 0);
     }
+
     EatMode::All
 }
 
 
-pub(crate) fn cmd_whisper_here(_arg: &[String]) -> EatMode {
-    let new: bool = get_pref_int("PREF_whispers_in_current").unwrap_or(0) == 0;
+pub(crate) fn cmd_gifts(_arg: &[String]) -> EatMode {
+    let new: bool = get_pref_int("PREF_giftbomb_expand").unwrap_or(0) == 0;
 
-    if set_pref_int("PREF_whispers_in_current", new.into()).is_ok() {
+    if set_pref_int("PREF_giftbomb_expand", new.into()).is_ok() {
         if new {
-            echo(EVENT_NORMAL, &["Twitch Whispers will also show in the current Tab."], 0);
+            echo(EVENT_NORMAL, &[
+                "Gift Bombs will now show every Gift on its own line.",
+            ], 0);
         } else {
-            echo(EVENT_NORMAL, &["Twitch Whispers will ONLY be shown in their own Tabs."], 0);
+            echo(EVENT_NORMAL, &[
+                "Gift Bombs will now list their recipients in one summary.",
+            ], 0);
         }
     } else {
         echo(EVENT_ERR, &["FAILED to set Preference."], 0);
@@ -290,3 +657,1159 @@ pub(crate) fn cmd_whisper_here(_arg: &[String]) -> EatMode {
 
     EatMode::All
 }
+
+
+pub(crate) fn cmd_helix(argslice: &[String]) -> EatMode {
+    let arg: Vec<&str> = argslice[1..].iter()
+        .take_while(|s| !s.is_empty())
+        .map(String::as_str)
+        .collect();
+
+    let key: String = match arg.first() {
+        Some(key) if helix::Config::KEYS.contains(&&*key.to_ascii_lowercase()) => {
+            key.to_ascii_lowercase()
+        }
+        Some(key) if key.eq_ignore_ascii_case("check") => {
+            helix::spawn(|api| {
+                let result = api.users(&[]);
+
+                Box::new(move || match result {
+                    Ok(users) if !users.is_empty() => {
+                        echo(EVENT_NORMAL, &[format!(
+                            "The Token belongs to {} (ID {}).",
+                            users[0].display_name,
+                            users[0].id,
+                        )], 0);
+                    }
+                    Ok(_) => { echo(EVENT_ERR, &["The Token belongs to no User."], 0); }
+                    Err(e) => { echo(EVENT_ERR, &[format!("FAILED to check Token: {}", e)], 0); }
+                })
+            });
+            return EatMode::All;
+        }
+        Some(key) if key.eq_ignore_ascii_case("reload") => {
+            match helix::Config::load() {
+                Ok(config) => {
+                    helix::HELIX.lock().config = config;
+                    echo(EVENT_NORMAL, &["API settings reloaded."], 0);
+                }
+                Err(e) => {
+                    echo(EVENT_ERR, &[format!("Failed to read API settings: {}", e)], 0);
+                }
+            }
+            return EatMode::All;
+        }
+        _ => {
+            echo(EVENT_ERR, &["Usage: HELIX check|reload | url|client|token [<value>|-]"], 0);
+            return EatMode::All;
+        }
+    };
+    let mut api = helix::HELIX.lock();
+
+    match arg.get(1) {
+        None => match api.config.get(&key) {
+            //  Do not print the Token itself.
+            Some(_) if key == "token" => { echo(EVENT_NORMAL, &["A Token is set."], 0); }
+            Some(value) => { echo(EVENT_NORMAL, &[format!("{} is: {}", key, value)], 0); }
+            None => { echo(EVENT_NORMAL, &[format!("{} is not set.", key)], 0); }
+        }
+        Some(value) => {
+            let value = Some(value.to_string()).filter(|v| v != "-");
+            api.config.set(&key, value);
+
+            match api.config.save() {
+                Ok(()) => { echo(EVENT_NORMAL, &["API settings saved."], 0); }
+                Err(e) => {
+                    echo(EVENT_ERR, &[format!("FAILED to save API settings: {}", e)], 0);
+                }
+            }
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_hilite(argslice: &[String]) -> EatMode {
+    let arg: Vec<&str> = argslice[1..].iter()
+        .take_while(|s| !s.is_empty())
+        .map(String::as_str)
+        .collect();
+
+    match arg.as_slice() {
+        [] | ["list"] => {
+            let highlights = filters::HIGHLIGHTS.read();
+
+            echo(EVENT_NORMAL, &["HIGHLIGHT RULES:"], 0);
+            for (i, rule) in highlights.rules().iter().enumerate() {
+                echo(EVENT_NORMAL, &[format!("{}: {}", i + 1, rule)], 0);
+            }
+        }
+        ["add", rest @ ..] => {
+            let mut rest = rest;
+            let mut copy = false;
+            let mut color: Option<u8> = None;
+
+            loop {
+                match rest {
+                    ["copy", tail @ ..] => {
+                        copy = true;
+                        rest = tail;
+                    }
+                    ["color", n, tail @ ..] => match n.parse::<u8>() {
+                        Ok(n) if n <= 3 => {
+                            color = Some(n);
+                            rest = tail;
+                        }
+                        _ => {
+                            echo(EVENT_ERR, &["Tab colors must be from 0 to 3."], 0);
+                            return EatMode::All;
+                        }
+                    }
+                    _ => break,
+                }
+            }
+
+            let (channel, kind, value) = match rest {
+                [channel, kind, value @ ..] => (channel, kind, value),
+                _ => {
+                    echo(EVENT_ERR, &[
+                        "Usage: HILITE add [copy] [color <0-3>] <channel|*> <kind> [<value>]",
+                    ], 0);
+                    return EatMode::All;
+                }
+            };
+
+            match filters::Matcher::new(kind, &value.join(" ")) {
+                Ok(matcher) => {
+                    let mut highlights = filters::HIGHLIGHTS.write();
+
+                    highlights.add(filters::Rule {
+                        channel: Some(channel)
+                            .filter(|&&ch| ch != "*")
+                            .map(|ch| format!("#{}", ch.trim_start_matches('#')).to_ascii_lowercase()),
+                        color,
+                        copy,
+                        matcher,
+                    });
+
+                    if highlights.save().is_ok() {
+                        echo(EVENT_NORMAL, &["Highlight Rule added."], 0);
+                    } else {
+                        echo(EVENT_ERR, &["FAILED to save Highlight Rules."], 0);
+                    }
+                }
+                Err(e) => { echo(EVENT_ERR, &[e], 0); }
+            }
+        }
+        ["del", n] => {
+            let mut highlights = filters::HIGHLIGHTS.write();
+
+            match n.parse().ok().and_then(|n| highlights.remove(n)) {
+                Some(rule) => if highlights.save().is_ok() {
+                    echo(EVENT_NORMAL, &[format!("Highlight Rule removed: {}", rule)], 0);
+                } else {
+                    echo(EVENT_ERR, &["FAILED to save Highlight Rules."], 0);
+                }
+                None => { echo(EVENT_ERR, &[format!("There is no Rule number {}.", n)], 0); }
+            }
+        }
+        _ => {
+            echo(EVENT_ERR, &[
+                "Usage: HILITE list | del <number> | \
+                add [copy] [color <0-3>] <channel|*> <kind> [<value>]",
+            ], 0);
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_ht_debug(_arg: &[String]) -> EatMode {
+    let new: bool = get_pref_int("PREF_htdebug").unwrap_or(0) == 0;
+
+    if set_pref_int("PREF_htdebug", new.into()).is_ok() {
+        if new {
+            echo(EVENT_NORMAL, &[
+                "Unrecognized UserNotices will now show the full Message.",
+            ], 0);
+        } else {
+            echo(EVENT_NORMAL, &[
+                "Unrecognized UserNotices will NOT show the full Message.",
+            ], 0);
+        }
+    } else {
+        echo(EVENT_ERR, &["FAILED to set Preference."], 0);
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_idle(argslice: &[String]) -> EatMode {
+    match argslice.get(1).map(String::as_str).unwrap_or_default() {
+        "" => {
+            let minutes = get_pref_int("PREF_idle_timeout")
+                .map_or(IDLE_DEFAULT, |m| m.max(0) as u64);
+
+            if minutes == 0 {
+                echo(EVENT_NORMAL, &["Idle Users are never removed."], 0);
+            } else {
+                echo(EVENT_NORMAL, &[format!(
+                    "Users are removed after {} minutes without speaking.",
+                    minutes,
+                )], 0);
+            }
+        }
+        arg => match arg.parse::<i32>().ok().filter(|&m| m >= 0) {
+            Some(minutes) if set_pref_int("PREF_idle_timeout", minutes).is_ok() => {
+                echo(EVENT_NORMAL, &["Preference set."], 0);
+            }
+            Some(_) => { echo(EVENT_ERR, &["FAILED to set Preference."], 0); }
+            None => { echo(EVENT_ERR, &["Usage: IDLETIME [<minutes>]"], 0); }
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_lag_mark(argslice: &[String]) -> EatMode {
+    match argslice.get(1).map(String::as_str).unwrap_or_default() {
+        "" => match get_pref_int("PREF_lag_mark").filter(|&n| n > 0) {
+            Some(secs) => {
+                echo(EVENT_NORMAL, &[format!(
+                    "Messages more than {} seconds late will be marked.",
+                    secs,
+                )], 0);
+            }
+            None => { echo(EVENT_NORMAL, &["Late Messages are not marked."], 0); }
+        }
+        arg => match arg.parse::<i32>().ok().filter(|&s| s >= 0) {
+            Some(secs) if set_pref_int("PREF_lag_mark", secs).is_ok() => {
+                echo(EVENT_NORMAL, &["Preference set."], 0);
+            }
+            Some(_) => { echo(EVENT_ERR, &["FAILED to set Preference."], 0); }
+            None => { echo(EVENT_ERR, &["Usage: LAGMARK [<seconds>]"], 0); }
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_notices(_arg: &[String]) -> EatMode {
+    match notices::TEMPLATES.write().reload() {
+        Ok(count) => {
+            echo(EVENT_NORMAL, &[format!(
+                "UserNotice templates reloaded, with {} override{} from {:?}.",
+                count,
+                if count == 1 { "" } else { "s" },
+                notices::TEMPLATE_FILE,
+            )], 0);
+        }
+        Err(e) => {
+            echo(EVENT_ERR, &[format!("FAILED to read templates: {}", e)], 0);
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_raid_greeting(argslice: &[String]) -> EatMode {
+    let arg: Vec<&str> = argslice[1..].iter()
+        .take_while(|s| !s.is_empty())
+        .map(String::as_str)
+        .collect();
+
+    match arg.as_slice() {
+        [] => match get_pref_string("PREF_raid_greeting").filter(|g| !g.is_empty()) {
+            Some(greeting) => {
+                echo(EVENT_NORMAL, &[format!("Raids are greeted with: {}", greeting)], 0);
+            }
+            None => { echo(EVENT_NORMAL, &["Raids are not greeted."], 0); }
+        }
+        ["-"] => if delete_pref("PREF_raid_greeting").is_ok() {
+            echo(EVENT_NORMAL, &["Preference set."], 0);
+        } else {
+            echo(EVENT_ERR, &["FAILED to set Preference."], 0);
+        }
+        words => if set_pref_string("PREF_raid_greeting", &words.join(" ")).is_ok() {
+            echo(EVENT_NORMAL, &["Preference set."], 0);
+        } else {
+            echo(EVENT_ERR, &["FAILED to set Preference."], 0);
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_raiders(argslice: &[String]) -> EatMode {
+    let minutes: u64 = match argslice.get(1).map(String::as_str).unwrap_or_default() {
+        "" => RAIDERS_DEFAULT,
+        n => match n.parse() {
+            Ok(n) => n,
+            Err(_) => {
+                echo(EVENT_ERR, &["Usage: RAIDERS [<minutes>]"], 0);
+                return EatMode::All;
+            }
+        }
+    };
+    let window: Duration = match minutes.checked_mul(60) {
+        Some(secs) => Duration::from_secs(secs),
+        None => {
+            echo(EVENT_ERR, &["Usage: RAIDERS [<minutes>]"], 0);
+            return EatMode::All;
+        }
+    };
+
+    match RAIDS.read().get(&get_channel_name()) {
+        Some(raid) => {
+            let users = raid.chatters(window);
+
+            echo(EVENT_NORMAL, &[format!(
+                "{} of {} Raiders from #{} chatted within {} minutes: {}",
+                users.len(),
+                raid.viewers,
+                raid.raider,
+                minutes,
+                users.join(", "),
+            )], 0);
+        }
+        None => { echo(EVENT_NORMAL, &["This Channel has not been Raided."], 0); }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_reward(argslice: &[String]) -> EatMode {
+    let arg: Vec<&str> = argslice[1..].iter()
+        .take_while(|s| !s.is_empty())
+        .map(String::as_str)
+        .collect();
+    let len = arg.len();
+
+    if len < 1 {
+        //  Print the current Reward Names.
+        echo(EVENT_NORMAL, &["REWARD EVENTS:"], 0);
+        for pref in get_prefs() {
+            if !pref.is_empty() && !pref.starts_with("PREF") {
+                echo(
+                    EVENT_NORMAL,
+                    &[format!(
+                        "{}: '{}'",
+                        pref,
+                        get_pref_string(&pref)
+                            .unwrap_or_default(),
+                    )],
+                    0,
+                );
+            }
+        }
+    } else if {
+        !arg[0].starts_with("PREF")
+            && {
+            if len < 2 {
+                //  Unset a Reward.
+                delete_pref(&arg[0].to_lowercase())
+            } else {
+                //  Set a Reward.
+                set_pref_string(
+                    &arg[0].to_lowercase(),
+                    &arg[1..].join(" ").trim(),
+                )
+            }
+        }.is_ok()
+    } {
+        echo(EVENT_NORMAL, &["Preference set."], 0);
+    } else {
+        echo(EVENT_ERR, &["FAILED to set Preference."], 0);
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_stream_poll(argslice: &[String]) -> EatMode {
+    match argslice.get(1).map(String::as_str).unwrap_or_default() {
+        "" => match get_pref_int("PREF_stream_poll").map_or(POLL_DEFAULT, |s| s.max(0) as u64) {
+            0 => { echo(EVENT_NORMAL, &["Streams are not polled."], 0); }
+            secs => {
+                echo(EVENT_NORMAL, &[format!(
+                    "Streams are polled every {} seconds.",
+                    secs,
+                )], 0);
+            }
+        }
+        arg => match arg.parse::<i32>().ok().filter(|&s| s >= 0) {
+            Some(secs) if set_pref_int("PREF_stream_poll", secs).is_ok() => {
+                echo(EVENT_NORMAL, &["Preference set."], 0);
+            }
+            Some(_) => { echo(EVENT_ERR, &["FAILED to set Preference."], 0); }
+            None => { echo(EVENT_ERR, &["Usage: STREAMPOLL [<seconds>]"], 0); }
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_tabcolor(argslice: &[String]) -> EatMode {
+    let arg: Vec<&str> = argslice[1..].iter()
+        .take_while(|s| !s.is_empty())
+        .map(String::as_str)
+        .collect();
+
+    if arg.is_empty() {
+        //  Print the current Policy, with any Channel overrides.
+        echo(EVENT_NORMAL, &["TAB COLORS:"], 0);
+        for cat in Category::ALL {
+            let global = cat.pref(None);
+            echo(EVENT_NORMAL, &[format!(
+                "{}: {}",
+                cat.name(),
+                get_pref_int(&global).unwrap_or(cat.default_color() as i32),
+            )], 0);
+
+            let prefix = format!("{}_", global);
+            for pref in get_prefs() {
+                if let Some(channel) = pref.strip_prefix(&prefix) {
+                    echo(EVENT_NORMAL, &[format!(
+                        "{} in {}: {}",
+                        cat.name(),
+                        channel,
+                        get_pref_int(&pref).unwrap_or_default(),
+                    )], 0);
+                }
+            }
+        }
+    } else if let Some(cat) = Category::from_name(arg[0]) {
+        let channel: Option<String> = arg.get(2)
+            .map(|ch| format!("#{}", ch.trim_start_matches('#')));
+        let pref = cat.pref(channel.as_deref());
+
+        let result = match arg.get(1).copied() {
+            None => {
+                echo(EVENT_NORMAL, &[format!(
+                    "{}: {}",
+                    cat.name(),
+                    cat.color(),
+                )], 0);
+                return EatMode::All;
+            }
+            Some("-") => delete_pref(&pref),
+            Some(level) => match level.parse::<u8>() {
+                Ok(n) if n <= 3 => set_pref_int(&pref, n as i32),
+                _ => {
+                    echo(EVENT_ERR, &["Color must be a number from 0 to 3."], 0);
+                    return EatMode::All;
+                }
+            },
+        };
+
+        if result.is_ok() {
+            echo(EVENT_NORMAL, &["Preference set."], 0);
+        } else {
+            echo(EVENT_ERR, &["FAILED to set Preference."], 0);
+        }
+    } else {
+        echo(EVENT_ERR, &[format!(
+            "Unknown Category. Categories are: {}",
+            Category::ALL.iter()
+                .map(|cat| cat.name())
+                .collect::<Vec<&str>>()
+                .join(", "),
+        )], 0);
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_title(argslice: &[String]) -> EatMode {
+    let arg: Vec<&str> = argslice[1..].iter()
+        .take_while(|s| !s.is_empty())
+        .map(String::as_str)
+        .collect();
+
+    if arg.len() < 2 {
+        echo(EVENT_ERR, &["Usage: TITLE <channel> <title>"], 0);
+        return EatMode::All;
+    }
+
+    let channel: String = format!(
+        "#{}",
+        arg[0].trim_start_matches('#').to_ascii_lowercase(),
+    );
+    let title: String = arg[1..].join(" ");
+
+    let room_id: String = match ROOM_IDS.read().get(&channel) {
+        Some(id) => id.to_owned(),
+        None => {
+            echo(EVENT_ERR, &[format!("The ID of {} is not known.", channel)], 0);
+            return EatMode::All;
+        }
+    };
+
+    helix::spawn(move |api| {
+        let result = set_title(api, &room_id, &title);
+
+        Box::new(move || match result {
+            Ok(()) => {
+                send_command(&format!(
+                    "RECV :Twitch@twitch.tv TOPIC {} :{}",
+                    channel,
+                    title,
+                ));
+            }
+            Err(e) => {
+                echo(EVENT_ERR, &[format!("FAILED to set Title: {}", e)], 0);
+            }
+        })
+    });
+
+    EatMode::All
+}
+
+
+/// Set the Title of a Channel through the API.
+///
+/// Input: `&mut dyn HelixBackend`, `&str`, `&str`
+/// Return: `Result<(), HelixError>`
+fn set_title(
+    api: &mut dyn helix::HelixBackend,
+    room_id: &str,
+    title: &str,
+) -> Result<(), helix::HelixError> {
+    api.modify_channel(room_id, title)
+}
+
+
+pub(crate) fn cmd_tgroup(argslice: &[String]) -> EatMode {
+    let arg: Vec<&str> = argslice[1..].iter()
+        .take_while(|s| !s.is_empty())
+        .map(String::as_str)
+        .collect();
+
+    match arg.as_slice() {
+        [] => {
+            echo(EVENT_NORMAL, &["CHANNEL GROUPS:"], 0);
+            for pref in get_prefs() {
+                if let Some(name) = pref.strip_prefix("PREF_group_") {
+                    echo(EVENT_NORMAL, &[format!(
+                        "@{}: {}",
+                        name,
+                        get_pref_string(&pref).unwrap_or_default(),
+                    )], 0);
+                }
+            }
+        }
+        [name] => match get_pref_string(&format!("PREF_group_{}", name.to_ascii_lowercase())) {
+            Some(list) => { echo(EVENT_NORMAL, &[format!("@{}: {}", name, list)], 0); }
+            None => { echo(EVENT_ERR, &[format!("There is no group named '{}'.", name)], 0); }
+        }
+        [name, "-"] => if delete_pref(
+            &format!("PREF_group_{}", name.to_ascii_lowercase())
+        ).is_ok() {
+            echo(EVENT_NORMAL, &["Preference set."], 0);
+        } else {
+            echo(EVENT_ERR, &["FAILED to set Preference."], 0);
+        }
+        [name, words @ ..] => match outgoing::channel_list(words, |_| None) {
+            Ok(channels) => if set_pref_string(
+                &format!("PREF_group_{}", name.to_ascii_lowercase()),
+                &channels.join(","),
+            ).is_ok() {
+                echo(EVENT_NORMAL, &["Preference set."], 0);
+            } else {
+                echo(EVENT_ERR, &["FAILED to set Preference."], 0);
+            }
+            Err(e) => { echo(EVENT_ERR, &[e], 0); }
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_tignore(argslice: &[String]) -> EatMode {
+    let arg: Vec<&str> = argslice[1..].iter()
+        .take_while(|s| !s.is_empty())
+        .map(String::as_str)
+        .collect();
+
+    match arg.as_slice() {
+        [] | ["list"] => {
+            let ignores = filters::IGNORES.read();
+
+            echo(EVENT_NORMAL, &["IGNORE RULES:"], 0);
+            for (i, rule) in ignores.rules().iter().enumerate() {
+                echo(EVENT_NORMAL, &[format!("{}: {}", i + 1, rule)], 0);
+            }
+            echo(EVENT_NORMAL, &[format!(
+                "{} Message{} hidden this session.",
+                ignores.count(),
+                if ignores.count() == 1 { "" } else { "s" },
+            )], 0);
+        }
+        ["shown", rest @ ..] if rest.len() < 2 => {
+            let ignores = filters::IGNORES.read();
+            let count: usize = match rest.first().map(|n| n.parse()) {
+                None => filters::HIDDEN_LIMIT,
+                Some(Ok(n)) => n,
+                Some(Err(_)) => {
+                    echo(EVENT_ERR, &["Usage: TIGNORE shown [<count>]"], 0);
+                    return EatMode::All;
+                }
+            };
+            let hidden: Vec<&filters::Hidden> = ignores.hidden().collect();
+
+            echo(EVENT_NORMAL, &["HIDDEN MESSAGES:"], 0);
+            for msg in &hidden[hidden.len().saturating_sub(count)..] {
+                echo(EVENT_NORMAL, &[format!(
+                    "[{}] {} <{}> {}",
+                    msg.time.with_timezone(&Local).format("%H:%M:%S"),
+                    msg.channel,
+                    msg.author,
+                    msg.text,
+                )], 0);
+            }
+        }
+        ["add", channel, kind, value @ ..] => {
+            let mut value: String = value.join(" ");
+
+            //  A User may be named by login, as long as their ID is known.
+            if kind.eq_ignore_ascii_case("id")
+                && !value.bytes().all(|b| b.is_ascii_digit())
+            {
+                match PROFILES.read().get(value.trim_start_matches('@'))
+                    .map(|p| p.user_id.clone())
+                    .filter(|id| !id.is_empty())
+                {
+                    Some(id) => value = id,
+                    None => {
+                        echo(EVENT_ERR, &[format!(
+                            "The ID of {} is not known; use their login with \"author\".",
+                            value,
+                        )], 0);
+                        return EatMode::All;
+                    }
+                }
+            }
+
+            match filters::Matcher::new(kind, &value) {
+                Ok(matcher) => {
+                    let mut ignores = filters::IGNORES.write();
+
+                    ignores.add(filters::Ignore {
+                        channel: Some(channel)
+                            .filter(|&&ch| ch != "*")
+                            .map(|ch| format!("#{}", ch.trim_start_matches('#')).to_ascii_lowercase()),
+                        matcher,
+                    });
+
+                    if ignores.save().is_ok() {
+                        echo(EVENT_NORMAL, &["Ignore Rule added."], 0);
+                    } else {
+                        echo(EVENT_ERR, &["FAILED to save Ignore Rules."], 0);
+                    }
+                }
+                Err(e) => { echo(EVENT_ERR, &[e], 0); }
+            }
+        }
+        ["del", n] => {
+            let mut ignores = filters::IGNORES.write();
+
+            match n.parse().ok().and_then(|n| ignores.remove(n)) {
+                Some(rule) => if ignores.save().is_ok() {
+                    echo(EVENT_NORMAL, &[format!("Ignore Rule removed: {}", rule)], 0);
+                } else {
+                    echo(EVENT_ERR, &["FAILED to save Ignore Rules."], 0);
+                }
+                None => { echo(EVENT_ERR, &[format!("There is no Rule number {}.", n)], 0); }
+            }
+        }
+        _ => {
+            echo(EVENT_ERR, &[
+                "Usage: TIGNORE list | shown [<count>] | del <number> | \
+                add <channel|*> <kind> [<value>]",
+            ], 0);
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_tjoin(argslice: &[String]) -> EatMode {
+    if !get_network_name().unwrap_or_default().eq_ignore_ascii_case("twitch") {
+        echo(EVENT_ERR, &["Channels may only be joined this way on Twitch."], 0);
+        return EatMode::All;
+    }
+
+    let arg: Vec<&str> = argslice[1..].iter()
+        .take_while(|s| !s.is_empty())
+        .map(String::as_str)
+        .collect();
+
+    let channels = match outgoing::channel_list(&arg, |name| {
+        get_pref_string(&format!("PREF_group_{}", name))
+    }) {
+        Ok(channels) if !channels.is_empty() => channels,
+        Ok(_) => {
+            echo(EVENT_ERR, &["Usage: TWITCHJOIN <channel>[,<channel>...]|@<group>"], 0);
+            return EatMode::All;
+        }
+        Err(e) => {
+            echo(EVENT_ERR, &[e], 0);
+            return EatMode::All;
+        }
+    };
+
+    let origin = get_channel_name();
+    {
+        let mut joins = outgoing::JOINS.lock();
+
+        for channel in channels { joins.push(&origin, channel); }
+    }
+
+    outgoing::flush();
+
+    let waiting = outgoing::JOINS.lock().len();
+    if waiting > 0 {
+        echo(EVENT_NORMAL, &[format!(
+            "{} more Channel{} will be joined shortly.",
+            waiting,
+            if waiting == 1 { "" } else { "s" },
+        )], 0);
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_twhois(argslice: &[String]) -> EatMode {
+    let user: String = argslice.get(1)
+        .map(|s| s.trim_start_matches('@').to_ascii_lowercase())
+        .unwrap_or_default();
+
+    if user.is_empty() {
+        echo(EVENT_ERR, &["Usage: TWHOIS <user>"], 0);
+        return EatMode::All;
+    }
+
+    //  Details of the Account are only requested if the API is set up. The
+    //      request is made on a worker thread, and the WHOIS printed after.
+    let configured = {
+        let api = helix::HELIX.lock();
+        api.config.client_id.is_some() && api.config.token.is_some()
+    };
+
+    if configured {
+        let origin = get_channel_name();
+
+        helix::spawn(move |api| {
+            let account = lookup_account(api, &user);
+
+            Box::new(move || {
+                if !in_channel(&origin, || print_whois(&user, Some(&account))) {
+                    print_whois(&user, Some(&account));
+                }
+            })
+        });
+    } else {
+        print_whois(&user, None);
+    }
+
+    EatMode::All
+}
+
+
+/// Look up the Account of a User through the API.
+///
+/// Input: `&mut dyn HelixBackend`, `&str`
+/// Return: `Result<Option<User>, HelixError>`
+fn lookup_account(
+    api: &mut dyn helix::HelixBackend,
+    user: &str,
+) -> Result<Option<helix::User>, helix::HelixError> {
+    api.users(&[user]).map(|mut users| users.pop())
+}
+
+
+/// Print what is known about a User, from their Profile, and from their Account
+///     if it was looked up.
+///
+/// Input: `&str`, `Option<&Result<Option<User>, HelixError>>`
+fn print_whois(
+    user: &str,
+    account: Option<&Result<Option<helix::User>, helix::HelixError>>,
+) {
+    let profiles = PROFILES.read();
+    let profile = profiles.get(user);
+    let found = match account {
+        Some(Ok(Some(found))) => Some(found),
+        _ => None,
+    };
+
+    if profile.is_none() && found.is_none() {
+        echo(EVENT_ERR, &[format!("Nothing is known about {}.", user)], 0);
+        if let Some(Err(e)) = account {
+            echo(EVENT_ERR, &[format!("FAILED to look up Account: {}", e)], 0);
+        }
+        return;
+    }
+
+    let nick: &str = match (profile, found) {
+        (Some(p), _) if !p.display.is_empty() => &p.display,
+        (_, Some(acc)) => &acc.display_name,
+        _ => user,
+    };
+    let user_id: &str = match (profile, found) {
+        (Some(p), _) if !p.user_id.is_empty() => &p.user_id,
+        (_, Some(acc)) => &acc.id,
+        _ => "",
+    };
+    let special = |text: String| echo(PrintEvent::WHOIS_SPECIAL, &[nick, &text, ""], 0);
+
+    echo(PrintEvent::WHOIS_NAME_LINE, &[
+        nick,
+        user,
+        "twitch.tv",
+        &format!("ID {}", if user_id.is_empty() { "unknown" } else { user_id }),
+    ], 0);
+
+    if let Some(profile) = profile {
+        let channels = profile.sorted();
+
+        echo(PrintEvent::WHOIS_CHANNEL_OPER_LINE, &[
+            nick,
+            &channels.iter().map(|(ch, p)| format!(
+                "{}{}",
+                Badges::from_str(&p.badges, &p.badge_info).output,
+                ch,
+            )).collect::<Vec<_>>().join(" "),
+        ], 0);
+
+        if let Some(last) = channels.iter().filter_map(|(_, p)| p.last_seen).max() {
+            let idle = Utc::now().signed_duration_since(last).num_seconds().max(0);
+            echo(PrintEvent::WHOIS_IDLE_LINE, &[
+                nick,
+                &format!("{:02}:{:02}:{:02}", idle / 3600, idle / 60 % 60, idle % 60),
+            ], 0);
+        }
+
+        if !profile.color.is_empty() {
+            special(format!("uses the color {}", profile.color));
+        }
+
+        for (channel, presence) in channels {
+            special(format!(
+                "has sent {} message{} in {} this session",
+                presence.messages,
+                if presence.messages == 1 { "" } else { "s" },
+                channel,
+            ));
+
+            if presence.deleted > 0 {
+                special(format!(
+                    "has had {} message{} deleted in {}",
+                    presence.deleted,
+                    if presence.deleted == 1 { "" } else { "s" },
+                    channel,
+                ));
+            }
+
+            for action in &presence.actions {
+                let time = action.time.with_timezone(&Local).format("%H:%M:%S");
+                let mut text = match action.duration {
+                    Some(secs) => format!("was timed out for {}s in {} at {}", secs, channel, time),
+                    None => format!("was banned from {} at {}", channel, time),
+                };
+
+                if !action.reason.is_empty() {
+                    text.push_str(&format!(": {}", action.reason));
+                }
+
+                special(text);
+            }
+        }
+    }
+
+    match account {
+        Some(Ok(Some(acc))) => {
+            if !acc.broadcaster_type.is_empty() {
+                special(format!("is a Twitch {}", acc.broadcaster_type));
+            }
+            if !acc.created_at.is_empty() {
+                special(format!("created their Account at {}", acc.created_at));
+            }
+            if !acc.description.is_empty() {
+                special(format!("describes themselves: {}", acc.description));
+            }
+        }
+        Some(Ok(None)) => { special(String::from("has no Account on Twitch")); }
+        Some(Err(e)) => { special(format!("could not be looked up: {}", e)); }
+        None => {}
+    }
+
+    echo(PrintEvent::WHOIS_END, &[nick], 0);
+}
+
+
+pub(crate) fn cmd_unread(_arg: &[String]) -> EatMode {
+    //  Release the lock before printing, which colors the Tab through it.
+    let unread = TABCOLORS.read().unread("Twitch");
+
+    if unread.is_empty() {
+        echo(EVENT_NORMAL, &["No Twitch Tabs have unread Events."], 0);
+    } else {
+        echo(EVENT_NORMAL, &["UNREAD TABS:"], 0);
+        for (tab, color) in unread {
+            echo(EVENT_NORMAL, &[format!("{}: {}", tab, color)], 0);
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_whisper(arg: &[String]) -> EatMode {
+    if arg.len() > 1
+        && get_network_name().unwrap_or_default()
+        .eq_ignore_ascii_case("twitch")
+    {
+        let targ: &str = &arg[1];
+
+        //  Two stage assignment to prevent Temporary Value.
+        let tmp: String = arg[2..].join(" ");
+        let msg: &str = tmp.trim();
+
+        //  Check for trailing Arguments.
+        if msg.is_empty() {
+            //  None: Switch to Whisper Tab.
+            send_command(&format!("QUERY {}", targ));
+        } else if !whispers::send(targ, msg) {
+            //  Some: Send through Whisper. This fails if there is no Channel to
+            //      send it from.
+            echo(EVENT_ERR, &["Whispers can only be sent after joining a Channel."], 0);
+        }
+    }
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_whistory(arg: &[String]) -> EatMode {
+    let user: &str = arg.get(1).map_or("", |s| s.trim_start_matches('@'));
+
+    if user.is_empty() {
+        echo(EVENT_ERR, &["Usage: WHISTORY <user> [<count> [<offset>]]"], 0);
+        return EatMode::All;
+    }
+
+    let number = |idx: usize, default: usize| -> Option<usize> {
+        match arg.get(idx).map(String::as_str).unwrap_or_default() {
+            "" => Some(default),
+            n => n.parse().ok(),
+        }
+    };
+
+    let (count, offset): (usize, usize) = match (number(2, 20), number(3, 0)) {
+        (Some(count), Some(offset)) => (count, offset),
+        _ => {
+            echo(EVENT_ERR, &["Usage: WHISTORY <user> [<count> [<offset>]]"], 0);
+            return EatMode::All;
+        }
+    };
+
+    match whispers::history(user, count, offset) {
+        Ok(entries) if entries.is_empty() => {
+            echo(EVENT_NORMAL, &[format!("No Whispers with {} are logged.", user)], 0);
+        }
+        Ok(entries) => {
+            let tab = get_current_channel();
+            for entry in entries { entry.replay(&tab); }
+        }
+        Err(e) => {
+            echo(EVENT_ERR, &[format!("Failed to read Whisper log: {}", e)], 0);
+        }
+    }
+
+    EatMode::All
+}
+
+
+pub(crate) fn cmd_whisper_here(argslice: &[String]) -> EatMode {
+    let arg: Vec<&str> = argslice[1..].iter()
+        .take_while(|s| !s.is_empty())
+        .map(String::as_str)
+        .collect();
+
+    let result = match arg.as_slice() {
+        [] => {
+            //  Toggle the default between the current Tab and only their own.
+            let new = if Route::default_route() == Route::Always {
+                Route::Tab
+            } else {
+                Route::Always
+            };
+
+            set_pref_string(&Route::pref(None), new.name())
+        }
+        ["list"] => {
+            echo(EVENT_NORMAL, &[format!(
+                "Default Whisper Route: {}",
+                Route::default_route().name(),
+            )], 0);
+
+            let prefix = Route::pref(Some(""));
+            for pref in get_prefs() {
+                if let Some(user) = pref.strip_prefix(&prefix) {
+                    echo(EVENT_NORMAL, &[format!(
+                        "{}: {}",
+                        user,
+                        Route::for_user(user).name(),
+                    )], 0);
+                }
+            }
+            return EatMode::All;
+        }
+        ["-", user] => delete_pref(&Route::pref(Some(user))),
+        [name, rest @ ..] if rest.len() < 2 => match Route::from_name(name) {
+            Some(route) => set_pref_string(
+                &Route::pref(rest.first().copied()),
+                route.name(),
+            ),
+            None => {
+                echo(EVENT_ERR, &[format!(
+                    "Unknown Route. Routes are: {}",
+                    Route::ALL.iter()
+                        .map(|r| r.name())
+                        .collect::<Vec<&str>>()
+                        .join(", "),
+                )], 0);
+                return EatMode::All;
+            }
+        }
+        _ => {
+            echo(EVENT_ERR, &["Usage: WHISPERHERE [list | <route> [<user>] | - <user>]"], 0);
+            return EatMode::All;
+        }
+    };
+
+    if result.is_ok() {
+        match arg.as_slice() {
+            [] if Route::default_route() == Route::Always => {
+                echo(EVENT_NORMAL, &["Twitch Whispers will also show in the current Tab."], 0);
+            }
+            [] => {
+                echo(EVENT_NORMAL, &["Twitch Whispers will ONLY be shown in their own Tabs."], 0);
+            }
+            _ => { echo(EVENT_NORMAL, &["Preference set."], 0); }
+        }
+    } else {
+        echo(EVENT_ERR, &["FAILED to set Preference."], 0);
+    }
+
+    EatMode::All
+}
+
+
+#[cfg(test)]
+mod tests_helix_commands {
+    use super::*;
+    use helix::{Channel, HelixBackend, HelixError, Stream, User};
+
+    /// A stand-in for the API, with one known User, and recording requests.
+    #[derive(Default)]
+    struct Fake { requests: Vec<String>, down: bool }
+
+    impl Fake {
+        fn check(&self) -> Result<(), HelixError> {
+            if self.down {
+                Err(HelixError::Status(401, String::from("Invalid OAuth token")))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl HelixBackend for Fake {
+        fn users(&mut self, logins: &[&str]) -> Result<Vec<User>, HelixError> {
+            self.requests.push(format!("users {}", logins.join(",")));
+            self.check()?;
+            Ok(logins.iter().filter(|&&l| l == "asdf").map(|&l| User {
+                id: String::from("123"),
+                login: l.to_owned(),
+                display_name: String::from("Asdf"),
+                broadcaster_type: String::new(),
+                description: String::new(),
+                created_at: String::new(),
+            }).collect())
+        }
+
+        fn streams(&mut self, _logins: &[&str]) -> Result<Vec<Stream>, HelixError> {
+            Ok(Vec::new())
+        }
+
+        fn channels(&mut self, _ids: &[&str]) -> Result<Vec<Channel>, HelixError> {
+            Ok(Vec::new())
+        }
+
+        fn modify_channel(&mut self, id: &str, title: &str) -> Result<(), HelixError> {
+            self.requests.push(format!("modify {} {}", id, title));
+            self.check()
+        }
+    }
+
+    /// Test to confirm that TITLE sets the Title of the right Channel, and
+    ///     reports a failure.
+    #[test]
+    fn test_set_title() {
+        let mut api = Fake::default();
+
+        assert!(set_title(&mut api, "123", "New title").is_ok(), "Title not set.");
+        assert_eq!(
+            vec![String::from("modify 123 New title")],
+            api.requests,
+            "Title set through the wrong request.",
+        );
+
+        api.down = true;
+        assert!(
+            matches!(set_title(&mut api, "123", "New title"), Err(HelixError::Status(401, _))),
+            "Failure to set Title not reported.",
+        );
+    }
+
+    /// Test to confirm that TWHOIS finds a known Account, finds nothing for an
+    ///     unknown one, and reports a failure.
+    #[test]
+    fn test_lookup_account() {
+        let mut api = Fake::default();
+
+        match lookup_account(&mut api, "asdf") {
+            Ok(Some(user)) => assert_eq!("123", user.id, "Wrong Account found."),
+            _ => panic!("Known Account not found."),
+        }
+        assert!(
+            matches!(lookup_account(&mut api, "qwert"), Ok(None)),
+            "Unknown Account found.",
+        );
+        assert_eq!(
+            vec![String::from("users asdf"), String::from("users qwert")],
+            api.requests,
+            "Accounts looked up through the wrong requests.",
+        );
+
+        api.down = true;
+        assert!(
+            matches!(lookup_account(&mut api, "asdf"), Err(HelixError::Status(401, _))),
+            "Failure to look up Account not reported.",
+        );
+    }
+}
@@ -0,0 +1,546 @@
+//! Module for the rendering of UserNotices through templates. Each `msg-id` is
+//!     mapped to a template, in which placeholders like `{login}` are replaced
+//!     with the values of Tags. Built-in templates can be overridden, such as
+//!     to translate them, by lines of the form `key = template` in a file.
+
+use std::{collections::HashMap, io};
+
+use hexchat::PrintEvent;
+use parking_lot::RwLock;
+
+use super::{
+    gifts::Bomb,
+    irc::Message,
+    output::{Category, EVENT_ALERT, EVENT_NORMAL},
+    storage,
+};
+
+
+/// Name of the file, in the Plugin directory, which overrides templates.
+pub const TEMPLATE_FILE: &str = "notices.conf";
+
+
+/// NOTICES: The kinds of UserNotice which have templates, with the Event used
+///     to print them, the key of their label template if any, the Category of
+///     their Tab color, and the color used if none is set for the Category.
+static NOTICES: &[(&str, PrintEvent, Option<&str>, Category, u8)] = &[
+    ("announcement", EVENT_ALERT, Some("label.announcement"), Category::Highlight, 3),
+
+    ("raid", EVENT_NORMAL, None, Category::Raid, 1),
+    ("unraid", EVENT_NORMAL, None, Category::Raid, 1),
+
+    ("bitsbadgetier", EVENT_NORMAL, None, Category::Cheer, 1),
+    ("charity", EVENT_NORMAL, None, Category::Cheer, 1),
+    ("rewardgift", EVENT_NORMAL, None, Category::Reward, 1),
+    ("ritual", EVENT_NORMAL, None, Category::Reward, 1),
+    ("onetapgiftredeemed", EVENT_NORMAL, None, Category::Cheer, 1),
+    ("viewermilestone", EVENT_NORMAL, None, Category::Reward, 2),
+
+    ("sub", EVENT_ALERT, Some("label.sub"), Category::Sub, 2),
+    ("resub", EVENT_ALERT, Some("label.sub"), Category::Sub, 2),
+    ("extendsub", EVENT_ALERT, Some("label.sub"), Category::Sub, 2),
+    ("subgift", EVENT_ALERT, Some("label.sub"), Category::Sub, 2),
+    ("submysterygift", EVENT_ALERT, Some("label.sub"), Category::Sub, 2),
+
+    ("standardpayforward", EVENT_NORMAL, None, Category::Sub, 1),
+    ("communitypayforward", EVENT_NORMAL, None, Category::Sub, 1),
+
+    ("giftpaidupgrade", EVENT_ALERT, Some("label.upgrade"), Category::Sub, 2),
+    ("anongiftpaidupgrade", EVENT_ALERT, Some("label.upgrade"), Category::Sub, 2),
+    ("primepaidupgrade", EVENT_ALERT, Some("label.upgrade"), Category::Sub, 2),
+];
+
+
+/// DEFAULTS: The built-in templates. Keys which are a `msg-id` are the whole
+///     line for that UserNotice; Other keys are labels, variants, or fragments
+///     used by the computed placeholders.
+static DEFAULTS: &[(&str, &str)] = &[
+    ("label.sub", "SUBSCRIPTION"),
+    ("label.upgrade", "UPGRADE"),
+    ("label.announcement", "ANNOUNCEMENT"),
+    ("label.unknown", "UNKNOWN"),
+
+    ("announcement", "{color}<{login}>{message}\x0F"),
+    ("sharedchatnotice", "(Shared Chat) {value}"),
+
+    ("raid", "A raid of {msg-param-viewerCount} arrives from #{raider}{avatar}"),
+    ("unraid", "A raid has been canceled"),
+
+    ("bitsbadgetier", "{system-msg}"),
+    ("charity", "{system-msg}"),
+    ("rewardgift", "{system-msg}"),
+    ("ritual", "{system-msg}"),
+    ("onetapgiftredeemed", "<{msg-param-user-display-name}> sends a {msg-param-gift-id} for ({msg-param-bits-spent}) bits"),
+    ("viewermilestone", "<{login}> has watched ({msg-param-value}) consecutive streams{message}"),
+    ("viewermilestone.other", "{system-msg}"),
+
+    ("sub", "<{login}> subscribes{plan}{streak}{cumulative}{message}"),
+    ("resub", "<{login}> resubscribes{plan}{streak}{cumulative}{message}"),
+    ("extendsub", "<{login}> extends a sub{plan}{streak}{cumulative}{through}{message}"),
+    ("subgift", "<{msg-param-recipient-user-name}> is gifted a subscription by <{login}>{months}{cumulative}"),
+    ("submysterygift", "<{login}> gives out ({msg-param-mass-gift-count}) random gift subscriptions"),
+    ("submysterygift.one", "<{login}> gives out ({msg-param-mass-gift-count}) random gift subscription"),
+    ("giftbomb", "<{login}> gifted ({count}) subscriptions to: {recipients}"),
+
+    ("standardpayforward", "<{login}> pays forward a gift subscription from <{msg-param-prior-gifter-user-name}> to <{msg-param-recipient-user-name}>"),
+    ("standardpayforward.anon", "<{login}> pays forward an anonymous gift subscription to <{msg-param-recipient-user-name}>"),
+    ("communitypayforward", "<{login}> pays forward a gift subscription from <{msg-param-prior-gifter-user-name}> to the community"),
+    ("communitypayforward.anon", "<{login}> pays forward an anonymous gift subscription to the community"),
+
+    ("giftpaidupgrade", "<{login}> upgrades a gift subscription from <{msg-param-sender-login}>{promo}"),
+    ("anongiftpaidupgrade", "<{login}> upgrades an anonymous gift subscription{promo}"),
+    ("primepaidupgrade", "<{login}> upgrades a Prime subscription"),
+
+    //  Fragments for computed placeholders. `{value}` is the value computed.
+    ("plan.Prime", " with Prime"),
+    ("plan.1000", ""),
+    ("plan.2000", " at Tier 2 ($10)"),
+    ("plan.3000", " at Tier 3 ($25)"),
+    ("plan", " with plan \"{value}\""),
+    ("streak", " for ({value}) months in a row"),
+    ("cumulative", ", with ({value}) months in total"),
+    ("through", ", through {value}"),
+    ("message", ": {value}"),
+    ("promo", ", during {value}"),
+    //  The profile image of a raider, when Twitch sends one.
+    ("avatar", " ({value})"),
+
+    ("color.PRIMARY", ""),
+    ("color.BLUE", "\x0312"),
+    ("color.GREEN", "\x0303"),
+    ("color.ORANGE", "\x0307"),
+    ("color.PURPLE", "\x0306"),
+
+    ("month.1", "January"),
+    ("month.2", "February"),
+    ("month.3", "March"),
+    ("month.4", "April"),
+    ("month.5", "May"),
+    ("month.6", "June"),
+    ("month.7", "July"),
+    ("month.8", "August"),
+    ("month.9", "September"),
+    ("month.10", "October"),
+    ("month.11", "November"),
+    ("month.12", "December"),
+];
+
+
+/// Templates: The templates in use, keyed by name. Built from the defaults,
+///     with any overrides from the template file laid over them.
+pub struct Templates { inner: HashMap<String, String> }
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self {
+            inner: DEFAULTS.iter()
+                .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+        }
+    }
+}
+
+impl Templates {
+    /// Get a template by name.
+    ///
+    /// Input: `&str`
+    /// Return: `Option<&str>`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.inner.get(key).map(String::as_str)
+    }
+
+    /// Apply overrides, from lines of the form `key = template`. Blank lines,
+    ///     and lines starting with `#`, are skipped. Returns the number of
+    ///     templates set.
+    ///
+    /// Input: `&[String]`
+    /// Return: `usize`
+    pub fn apply(&mut self, lines: &[String]) -> usize {
+        let mut count = 0;
+
+        for line in lines {
+            let line = line.trim_start();
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            if let Some(idx) = line.find(" = ") {
+                self.inner.insert(
+                    line[..idx].trim().to_owned(),
+                    line[idx + 3..].to_owned(),
+                );
+                count += 1;
+            }
+        }
+
+        count
+    }
+
+    /// Rebuild the templates from the defaults and the template file. Returns
+    ///     the number of templates overridden.
+    ///
+    /// Return: `io::Result<usize>`
+    pub fn reload(&mut self) -> io::Result<usize> {
+        let lines = storage::read_lines(TEMPLATE_FILE)?;
+        *self = Self::default();
+        Ok(self.apply(&lines))
+    }
+
+    /// Fill a template, replacing each `{name}` with the value returned for it
+    ///     by a Function. If the Function returns `None` for any placeholder,
+    ///     the whole template fails.
+    ///
+    /// Input: `&str`, `FnMut(&str) -> Option<String>`
+    /// Return: `Option<String>`
+    pub fn fill(
+        template: &str,
+        mut lookup: impl FnMut(&str) -> Option<String>,
+    ) -> Option<String> {
+        let mut out = String::with_capacity(template.len() * 2);
+        let mut rest = template;
+
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            rest = &rest[open..];
+
+            match rest.find('}') {
+                Some(close) => {
+                    out.push_str(&lookup(&rest[1..close])?);
+                    rest = &rest[close + 1..];
+                }
+                None => break,
+            }
+        }
+
+        out.push_str(rest);
+        Some(out)
+    }
+
+    /// Fill a template for a UserNotice, replacing each placeholder with either
+    ///     a computed value or the value of a Tag of the same name.
+    ///
+    /// Input: `&str`, `&Message`
+    /// Return: `Option<String>`
+    pub fn fill_with(&self, template: &str, msg: &Message) -> Option<String> {
+        Self::fill(template, |name| {
+            self.computed(msg, name).or_else(|| msg.get_tag(name))
+        })
+    }
+
+    /// Fill a fragment template with a single value.
+    ///
+    /// Input: `&str`, `&str`
+    /// Return: `String`
+    fn fragment(&self, key: &str, value: &str) -> String {
+        Self::fill(
+            self.get(key).unwrap_or_default(),
+            |name| if name == "value" { Some(value.to_owned()) } else { None },
+        ).unwrap_or_default()
+    }
+
+    /// Compute a placeholder which is not simply a Tag. Computed placeholders
+    ///     are empty when the Tags they use are absent or not meaningful.
+    ///
+    /// Input: `&Message`, `&str`
+    /// Return: `Option<String>`
+    fn computed(&self, msg: &Message, name: &str) -> Option<String> {
+        let months = |tag: &str| msg.get_tag(tag)
+            .filter(|n| n.parse::<usize>().unwrap_or(0) > 1);
+
+        Some(match name {
+            "plan" => match msg.get_tag("msg-param-sub-plan") {
+                Some(plan) => match self.get(&format!("plan.{}", plan)) {
+                    Some(fixed) => fixed.to_owned(),
+                    None => self.fragment("plan", &plan),
+                },
+                None => String::new(),
+            },
+            "streak" => months("msg-param-streak-months")
+                .map(|n| self.fragment("streak", &n))
+                .unwrap_or_default(),
+            "months" => months("msg-param-months")
+                .map(|n| self.fragment("streak", &n))
+                .unwrap_or_default(),
+            "cumulative" => months("msg-param-cumulative-months")
+                .map(|n| self.fragment("cumulative", &n))
+                .unwrap_or_default(),
+            "through" => match msg.get_tag("msg-param-sub-benefit-end-month") {
+                Some(month) => self.fragment("through", self.get(
+                    &format!("month.{}", month),
+                ).unwrap_or(&month)),
+                None => String::new(),
+            },
+            "message" => if msg.trail.is_empty() {
+                String::new()
+            } else {
+                self.fragment("message", &msg.trail)
+            },
+            "promo" => match msg.get_tag("msg-param-promo-name") {
+                Some(promo) => self.fragment("promo", &promo),
+                None => String::new(),
+            },
+            "color" => msg.get_tag("msg-param-color")
+                .and_then(|c| self.get(&format!("color.{}", c)))
+                .unwrap_or_default()
+                .to_owned(),
+            "raider" => msg.get_tag("msg-param-login")
+                .or_else(|| msg.get_tag("msg-param-displayName"))?
+                .to_lowercase(),
+            "avatar" => match msg.get_tag("msg-param-profileImageURL") {
+                Some(url) => self.fragment("avatar", &url),
+                None => String::new(),
+            },
+            _ => return None,
+        })
+    }
+}
+
+
+safe_static! {
+    pub static lazy TEMPLATES: RwLock<Templates> = {
+        let mut templates = Templates::default();
+        templates.reload().ok();
+        RwLock::new(templates)
+    };
+}
+
+
+/// Rendered: A UserNotice turned into a line ready to be printed.
+#[derive(Debug, PartialEq)]
+pub struct Rendered {
+    pub event: PrintEvent,
+    pub label: Option<String>,
+    pub text: String,
+    /// The Category of the Tab color, with the color used if none is set for
+    ///     it, or `None` if the UserNotice is unknown.
+    pub category: Option<(Category, u8)>,
+}
+
+
+/// Check whether a `msg-id` has its own template, or is shared from another
+///     Channel and rendered by the template of its source.
+///
+/// Input: `&str`
+/// Return: `bool`
+pub fn is_known(stype: &str) -> bool {
+    stype == "sharedchatnotice" || NOTICES.iter().any(|&(id, ..)| id == stype)
+}
+
+
+/// Choose the template to use for a UserNotice. Some kinds of UserNotice have
+///     variants, depending on which Tags are present.
+///
+/// Input: `&str`, `&Message`
+/// Return: `String`
+fn template_key(stype: &str, msg: &Message) -> String {
+    match stype {
+        "submysterygift"
+        if msg.get_tag("msg-param-mass-gift-count").as_deref() == Some("1")
+        => String::from("submysterygift.one"),
+
+        "viewermilestone"
+        if msg.get_tag("msg-param-category").as_deref() != Some("watch-streak")
+        => String::from("viewermilestone.other"),
+
+        "standardpayforward" | "communitypayforward"
+        if msg.get_tag("msg-param-prior-gifter-user-name").is_none()
+        => format!("{}.anon", stype),
+
+        _ => stype.to_owned(),
+    }
+}
+
+
+/// Render a UserNotice into a line. A `msg-id` without a template is shown by
+///     its `system-msg`. A UserNotice shared from another Channel in Shared
+///     Chat is rendered as its original `source-msg-id`, and marked as shared.
+///     Returns `None` if the UserNotice is missing a Tag that its template
+///     needs.
+///
+/// Input: `&Message`, `&Templates`
+/// Return: `Option<Rendered>`
+pub fn render(msg: &Message, templates: &Templates) -> Option<Rendered> {
+    let stype = msg.get_tag("msg-id")?;
+
+    if stype == "sharedchatnotice" {
+        let mut line = render_as(&msg.get_tag("source-msg-id")?, msg, templates)?;
+        line.text = templates.fragment("sharedchatnotice", &line.text);
+        Some(line)
+    } else {
+        render_as(&stype, msg, templates)
+    }
+}
+
+
+/// Render a UserNotice into a line, as a specific `msg-id`.
+///
+/// Input: `&str`, `&Message`, `&Templates`
+/// Return: `Option<Rendered>`
+fn render_as(stype: &str, msg: &Message, templates: &Templates) -> Option<Rendered> {
+    match NOTICES.iter().find(|&&(id, ..)| id == stype) {
+        Some(&(_, event, label, category, level)) => {
+            let template = templates.get(&template_key(stype, msg))?;
+            let text = templates.fill_with(template, msg)?;
+
+            Some(Rendered {
+                event,
+                label: label.and_then(|key| templates.get(key)).map(String::from),
+                text,
+                category: Some((category, level)),
+            })
+        }
+        None => Some(Rendered {
+            event: EVENT_ALERT,
+            label: templates.get("label.unknown").map(String::from),
+            text: msg.get_tag("system-msg")?,
+            category: None,
+        }),
+    }
+}
+
+
+/// Render the summary of a Gift Bomb, listing the recipients of its Gifts.
+///
+/// Input: `&Bomb`, `&Templates`
+/// Return: `Option<String>`
+pub fn render_bomb(bomb: &Bomb, templates: &Templates) -> Option<String> {
+    Templates::fill(templates.get("giftbomb")?, |name| match name {
+        "login" => Some(bomb.sender.clone()),
+        "count" => Some(bomb.recipients.len().to_string()),
+        "recipients" => Some(bomb.recipients.join(", ")),
+        _ => None,
+    })
+}
+
+
+#[cfg(test)]
+mod tests_notices {
+    use std::time::Instant;
+
+    use super::*;
+    use super::super::gifts::Bombs;
+
+    const SAMPLES: &[(&str, &str)] = &[
+        (
+            r"@badge-info=subscriber/8;badges=subscriber/6;login=asdfqwert;msg-id=resub;msg-param-cumulative-months=8;msg-param-streak-months=2;msg-param-sub-plan=2000;system-msg=asdfqwert\ssubscribed :tmi.twitch.tv USERNOTICE #zxcv :hello",
+            "<asdfqwert> resubscribes at Tier 2 ($10) for (2) months in a row, with (8) months in total: hello",
+        ),
+        (
+            r"@login=asdfqwert;msg-id=sub;msg-param-cumulative-months=1;msg-param-sub-plan=Prime :tmi.twitch.tv USERNOTICE #zxcv",
+            "<asdfqwert> subscribes with Prime",
+        ),
+        (
+            r"@login=asdfqwert;msg-id=extendsub;msg-param-sub-benefit-end-month=4;msg-param-sub-plan=1000 :tmi.twitch.tv USERNOTICE #zxcv",
+            "<asdfqwert> extends a sub, through April",
+        ),
+        (
+            r"@login=asdfqwert;msg-id=submysterygift;msg-param-mass-gift-count=1 :tmi.twitch.tv USERNOTICE #zxcv",
+            "<asdfqwert> gives out (1) random gift subscription",
+        ),
+        (
+            r"@login=asdfqwert;msg-id=standardpayforward;msg-param-recipient-user-name=zxcv :tmi.twitch.tv USERNOTICE #zxcv",
+            "<asdfqwert> pays forward an anonymous gift subscription to <zxcv>",
+        ),
+        (
+            r"@msg-id=raid;msg-param-displayName=AsdfQwert;msg-param-viewerCount=42 :tmi.twitch.tv USERNOTICE #zxcv",
+            "A raid of 42 arrives from #asdfqwert",
+        ),
+        (
+            r"@msg-id=somethingnew;system-msg=Something\snew\shappened :tmi.twitch.tv USERNOTICE #zxcv",
+            "Something new happened",
+        ),
+        (
+            r"@badges=moderator/1;display-name=AsdfQwert;login=asdfqwert;msg-id=announcement;msg-param-color=PURPLE;system-msg= :tmi.twitch.tv USERNOTICE #zxcv :Hello chat",
+            "\x0306<asdfqwert>: Hello chat\x0F",
+        ),
+        (
+            r"@login=asdfqwert;msg-id=announcement;msg-param-color=PRIMARY :tmi.twitch.tv USERNOTICE #zxcv :Hello chat",
+            "<asdfqwert>: Hello chat\x0F",
+        ),
+        (
+            r"@login=asdfqwert;msg-id=viewermilestone;msg-param-category=watch-streak;msg-param-copoReward=350;msg-param-value=10;system-msg=AsdfQwert\swatched\s10\sconsecutive\sstreams :tmi.twitch.tv USERNOTICE #zxcv :hype",
+            "<asdfqwert> has watched (10) consecutive streams: hype",
+        ),
+        (
+            r"@login=asdfqwert;msg-id=viewermilestone;msg-param-category=something-else;msg-param-value=3;system-msg=AsdfQwert\sdid\sa\sthing :tmi.twitch.tv USERNOTICE #zxcv",
+            "AsdfQwert did a thing",
+        ),
+        (
+            r"@login=asdfqwert;msg-id=sharedchatnotice;msg-param-cumulative-months=1;msg-param-sub-plan=Prime;source-msg-id=sub;source-room-id=12345 :tmi.twitch.tv USERNOTICE #zxcv",
+            "(Shared Chat) <asdfqwert> subscribes with Prime",
+        ),
+        (
+            r"@login=asdfqwert;msg-id=onetapgiftredeemed;msg-param-bits-spent=100;msg-param-gift-id=heart;msg-param-user-display-name=AsdfQwert :tmi.twitch.tv USERNOTICE #zxcv",
+            "<AsdfQwert> sends a heart for (100) bits",
+        ),
+        (
+            r"@login=asdfqwert;msg-id=giftpaidupgrade;msg-param-promo-gift-total=3;msg-param-promo-name=Subtember;msg-param-sender-login=zxcv :tmi.twitch.tv USERNOTICE #zxcv",
+            "<asdfqwert> upgrades a gift subscription from <zxcv>, during Subtember",
+        ),
+        (
+            r"@msg-id=raid;msg-param-displayName=Äsdf;msg-param-login=asdfqwert;msg-param-profileImageURL=https://example.com/a.png;msg-param-viewerCount=7 :tmi.twitch.tv USERNOTICE #zxcv",
+            "A raid of 7 arrives from #asdfqwert (https://example.com/a.png)",
+        ),
+    ];
+
+    /// Test to confirm that the built-in templates render each sample into the
+    ///     expected line, with optional parts present only when their Tags are.
+    #[test]
+    fn test_render_defaults() {
+        let templates = Templates::default();
+
+        for (line, expected) in SAMPLES {
+            let msg: Message = line.parse().expect("Failed to parse sample.");
+            let rendered = render(&msg, &templates).expect("Failed to render sample.");
+
+            assert_eq!(*expected, rendered.text, "Sample renders incorrectly.");
+        }
+    }
+
+    /// Test to confirm that a missing Tag fails the template, rather than being
+    ///     rendered as an empty string.
+    #[test]
+    fn test_render_missing() {
+        let msg: Message = r"@msg-id=subgift;login=asdfqwert :tmi.twitch.tv USERNOTICE #zxcv"
+            .parse().expect("Failed to parse sample.");
+
+        assert_eq!(None, render(&msg, &Templates::default()));
+    }
+
+    /// Test to confirm that a Gift Bomb is summarized with its recipients.
+    #[test]
+    fn test_render_bomb() {
+        let mut bombs = Bombs::default();
+        let now = Instant::now();
+
+        bombs.open("#zxcv", "asdfqwert", None, 2, now);
+        bombs.gift("#zxcv", "asdfqwert", None, "one", now);
+        bombs.gift("#zxcv", "asdfqwert", None, "two", now);
+
+        let bomb = bombs.flush(Default::default(), now).pop()
+            .expect("Failed to flush Bomb.");
+
+        assert_eq!(
+            Some(String::from("<asdfqwert> gifted (2) subscriptions to: one, two")),
+            render_bomb(&bomb, &Templates::default()),
+        );
+    }
+
+    /// Test to confirm that overrides replace templates, as for a translation.
+    #[test]
+    fn test_render_override() {
+        let mut templates = Templates::default();
+        let lines: Vec<String> = vec![
+            String::from("# Deutsch"),
+            String::from("label.sub = ABONNEMENT"),
+            String::from("sub = <{login}> abonniert{plan}"),
+            String::from("plan.Prime =  mit Prime"),
+        ];
+
+        assert_eq!(3, templates.apply(&lines), "Wrong number of overrides.");
+
+        let msg: Message = SAMPLES[1].0.parse().expect("Failed to parse sample.");
+        let rendered = render(&msg, &templates).expect("Failed to render sample.");
+
+        assert_eq!("<asdfqwert> abonniert mit Prime", rendered.text);
+        assert_eq!(Some(String::from("ABONNEMENT")), rendered.label);
+    }
+}
@@ -0,0 +1,717 @@
+//! Module for commands sent to Twitch, paced to stay within its rate limits.
+//!     Messages are limited per Channel, and for the whole connection, by Token
+//!     Buckets; Joins are limited by a Bucket of their own. Anything which
+//!     cannot be sent yet waits in a queue, which is drained by a Timer.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+use hexchat::{get_pref_int, send_command};
+use parking_lot::Mutex;
+
+use super::{
+    output::{echo, EVENT_ERR, in_channel, USERSTATE},
+    whispers::{OUTBOX, parse_command},
+};
+
+
+/// Most Channels which may be Joined within the Join window.
+pub const JOIN_LIMIT: u32 = 20;
+/// Length of time within which at most `JOIN_LIMIT` Channels may be Joined.
+pub const JOIN_WINDOW: Duration = Duration::from_secs(10);
+
+/// Most Messages which may be sent within the Message window, normally.
+pub const MSG_LIMIT: u32 = 20;
+/// Most Messages which may be sent within the Message window, in a Channel in
+///     which the User is a Moderator or the Broadcaster.
+pub const MSG_LIMIT_MOD: u32 = 100;
+/// Length of time within which at most `MSG_LIMIT` Messages may be sent.
+pub const MSG_WINDOW: Duration = Duration::from_secs(30);
+
+/// Length of time within which Twitch rejects a Message identical to the last.
+pub const DUPE_WINDOW: Duration = Duration::from_secs(30);
+/// Suffix added to a repeated Message to make it differ from the last one. The
+///     Tag character is invisible in Twitch Chat.
+const DUPE_SUFFIX: &str = " \u{E0000}";
+
+
+/// TokenBucket: A limit on how many things may be done within a window of
+///     time. Half of the limit may be spent at once, and the other half is
+///     refilled evenly over the window, so that no window of that length can
+///     ever contain more than the limit.
+#[derive(Debug)]
+pub struct TokenBucket {
+    limit: u32,
+    capacity: u32,
+    tokens: u32,
+    per_token: Duration,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// Create a full Bucket.
+    ///
+    /// Input: `u32`, `Duration`, `Instant`
+    /// Return: `TokenBucket`
+    pub fn new(limit: u32, window: Duration, now: Instant) -> Self {
+        let capacity = (limit / 2).max(1);
+
+        Self {
+            limit,
+            capacity,
+            tokens: capacity,
+            per_token: window / limit.saturating_sub(capacity).max(1),
+            last: now,
+        }
+    }
+
+    /// Return: `u32`
+    pub fn limit(&self) -> u32 { self.limit }
+
+    /// Add the Tokens earned since the last refill.
+    ///
+    /// Input: `Instant`
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last);
+        let earned = (elapsed.as_nanos() / self.per_token.as_nanos()) as u32;
+
+        if self.tokens.saturating_add(earned) >= self.capacity {
+            self.tokens = self.capacity;
+            self.last = now;
+        } else {
+            self.tokens += earned;
+            self.last += self.per_token * earned;
+        }
+    }
+
+    /// Check whether there is a Token available, without taking it.
+    ///
+    /// Input: `Instant`
+    /// Return: `bool`
+    pub fn ready(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens > 0
+    }
+
+    /// Take a Token, if one is available.
+    ///
+    /// Input: `Instant`
+    /// Return: `bool`
+    pub fn take(&mut self, now: Instant) -> bool {
+        if self.ready(now) {
+            self.tokens -= 1;
+            true
+        } else { false }
+    }
+}
+
+
+/// JoinQueue: Channels waiting to be Joined, and the Channels which the User
+///     is currently in.
+#[derive(Default)]
+pub struct JoinQueue {
+    pending: VecDeque<String>,
+    bucket: Option<TokenBucket>,
+    /// The Tab from which the Joins were requested, to send them from.
+    origin: String,
+    joined: HashSet<String>,
+}
+
+impl JoinQueue {
+    /// Add a Channel to the end of the queue. Returns `false` if the Channel
+    ///     is already waiting, or the User is already in it.
+    ///
+    /// Input: `&str`, `String`
+    /// Return: `bool`
+    pub fn push(&mut self, origin: &str, channel: String) -> bool {
+        if self.pending.contains(&channel) || self.is_joined(&channel) { return false; }
+
+        self.origin = origin.to_owned();
+        self.pending.push_back(channel);
+        true
+    }
+
+    /// Take as many Channels from the queue as may be Joined now.
+    ///
+    /// Input: `Instant`
+    /// Return: `Vec<String>`
+    pub fn drain(&mut self, now: Instant) -> Vec<String> {
+        let bucket = self.bucket.get_or_insert_with(|| {
+            TokenBucket::new(JOIN_LIMIT, JOIN_WINDOW, now)
+        });
+        let mut out: Vec<String> = Vec::new();
+
+        while !self.pending.is_empty() && bucket.take(now) {
+            out.extend(self.pending.pop_front());
+        }
+
+        out
+    }
+
+    /// Return Channels taken by `drain()` to the front of the queue, in their
+    ///     order, because they could not be Joined.
+    ///
+    /// Input: `Vec<String>`
+    pub fn restore(&mut self, channels: Vec<String>) {
+        for channel in channels.into_iter().rev() {
+            if !self.pending.contains(&channel) { self.pending.push_front(channel); }
+        }
+    }
+
+    /// Record that the User has Joined a Channel.
+    ///
+    /// Input: `&str`
+    pub fn joined(&mut self, channel: &str) {
+        self.joined.insert(channel.to_ascii_lowercase());
+    }
+
+    /// Record that the User has left a Channel, or with `None`, every Channel.
+    ///
+    /// Input: `Option<&str>`
+    pub fn left(&mut self, channel: Option<&str>) {
+        match channel {
+            Some(channel) => { self.joined.remove(&channel.to_ascii_lowercase()); }
+            None => self.joined.clear(),
+        }
+    }
+
+    /// Input: `&str`
+    /// Return: `bool`
+    pub fn is_joined(&self, channel: &str) -> bool {
+        self.joined.contains(&channel.to_ascii_lowercase())
+    }
+
+    /// Find the Tabs from which Joins may be sent: the Tab they were requested
+    ///     from, and then every Channel the User is in.
+    ///
+    /// Return: `Vec<String>`
+    pub fn senders(&self) -> Vec<String> {
+        let mut tabs: Vec<String> = vec![self.origin.clone()];
+        tabs.extend(self.joined.iter().filter(|&ch| *ch != self.origin).cloned());
+        tabs
+    }
+
+    /// Return: `usize`
+    pub fn len(&self) -> usize { self.pending.len() }
+}
+
+
+/// Queue: Commands waiting to be sent as Messages, each from a Channel. Twitch
+///     limits the Messages of an account across all Channels, to the normal
+///     limit for Messages in Channels where the User is not a Moderator, and
+///     to the Moderator limit for all Messages.
+#[derive(Default)]
+pub struct Queue {
+    pending: VecDeque<(String, String)>,
+    /// The account limit for Messages in Channels where the User is normal.
+    global: Option<TokenBucket>,
+    /// The account limit for all Messages.
+    global_mod: Option<TokenBucket>,
+    channels: HashMap<String, TokenBucket>,
+    /// Whether the User has been warned that Messages are being held back.
+    throttled: bool,
+}
+
+impl Queue {
+    /// Add a Command to the end of the queue, to be sent from a Channel.
+    ///
+    /// Input: `&str`, `String`
+    pub fn push(&mut self, channel: &str, command: String) {
+        self.pending.push_back((channel.to_ascii_lowercase(), command));
+    }
+
+    /// Take as many Commands from the queue as may be sent now. The limit of
+    ///     each Channel depends on whether the User is a Moderator there, as
+    ///     found by a Function. Commands stay in order within each Channel.
+    ///
+    /// Input: `Instant`, `Fn(&str) -> bool`
+    /// Return: `Vec<(String, String)>`
+    pub fn drain(&mut self, now: Instant, is_mod: impl Fn(&str) -> bool) -> Vec<(String, String)> {
+        let global = self.global.get_or_insert_with(|| {
+            TokenBucket::new(MSG_LIMIT, MSG_WINDOW, now)
+        });
+        let global_mod = self.global_mod.get_or_insert_with(|| {
+            TokenBucket::new(MSG_LIMIT_MOD, MSG_WINDOW, now)
+        });
+        let mut blocked: Vec<String> = Vec::new();
+        let mut kept: VecDeque<(String, String)> = VecDeque::new();
+        let mut out: Vec<(String, String)> = Vec::new();
+
+        for (channel, command) in self.pending.drain(..) {
+            if blocked.contains(&channel) {
+                kept.push_back((channel, command));
+                continue;
+            }
+
+            let moderator = is_mod(&channel);
+            let limit = if moderator { MSG_LIMIT_MOD } else { MSG_LIMIT };
+            let bucket = self.channels.entry(channel.clone())
+                .or_insert_with(|| TokenBucket::new(limit, MSG_WINDOW, now));
+
+            if bucket.limit() != limit {
+                *bucket = TokenBucket::new(limit, MSG_WINDOW, now);
+            }
+
+            if bucket.ready(now)
+                && global_mod.ready(now)
+                && (moderator || global.ready(now))
+            {
+                bucket.take(now);
+                global_mod.take(now);
+                if !moderator { global.take(now); }
+
+                out.push((channel, command));
+            } else {
+                blocked.push(channel.clone());
+                kept.push_back((channel, command));
+            }
+        }
+
+        self.pending = kept;
+        out
+    }
+
+    /// Check whether the User should be warned that Messages are being held
+    ///     back. Returns the number waiting, only the first time it is checked
+    ///     after the queue begins to back up.
+    ///
+    /// Return: `Option<usize>`
+    pub fn warn(&mut self) -> Option<usize> {
+        if self.pending.is_empty() {
+            self.throttled = false;
+            None
+        } else if self.throttled {
+            None
+        } else {
+            self.throttled = true;
+            Some(self.pending.len())
+        }
+    }
+}
+
+
+/// Duplicates: The last Message sent in each Channel, and when it was sent.
+#[derive(Default)]
+pub struct Duplicates { last: HashMap<String, (String, Instant)> }
+
+impl Duplicates {
+    /// Record a Message about to be sent in a Channel. If it would be rejected
+    ///     as a duplicate of the last one, it is returned with a suffix added,
+    ///     so that it differs. Returns `None` if the Message may be sent
+    ///     unchanged.
+    ///
+    /// Input: `&str`, `&str`, `Instant`
+    /// Return: `Option<String>`
+    pub fn bypass(&mut self, channel: &str, text: &str, now: Instant) -> Option<String> {
+        let channel = channel.to_ascii_lowercase();
+        let repeated = matches!(
+            self.last.get(&channel),
+            Some((last, time)) if last == text
+                && now.saturating_duration_since(*time) < DUPE_WINDOW
+        );
+        let sent = if repeated {
+            format!("{}{}", text, DUPE_SUFFIX)
+        } else {
+            text.to_owned()
+        };
+
+        self.last.insert(channel, (sent.clone(), now));
+        Some(sent).filter(|s| s != text)
+    }
+
+    /// Handle text typed into a Channel, given as the words HexChat passes to
+    ///     a hook on plain input, where every word is part of the text. Empty
+    ///     words between others stand for repeated spaces, and are kept, while
+    ///     the empty words padding the end are not. Twitch Commands are never
+    ///     rejected as duplicates, so they are left alone. Returns the text to
+    ///     send instead, if it must be changed.
+    ///
+    /// Input: `&str`, `&[String]`, `Instant`
+    /// Return: `Option<String>`
+    pub fn input(&mut self, channel: &str, word: &[String], now: Instant) -> Option<String> {
+        let end = word.iter().rposition(|w| !w.is_empty())? + 1;
+        let text: String = word[..end].join(" ");
+
+        if text.trim().is_empty() || text.starts_with('.') || text.starts_with('/') {
+            None
+        } else {
+            self.bypass(channel, &text, now)
+        }
+    }
+}
+
+
+safe_static! {
+    pub static lazy DUPLICATES: Mutex<Duplicates> = Default::default();
+}
+
+
+safe_static! {
+    pub static lazy JOINS: Mutex<JoinQueue> = Default::default();
+}
+
+
+safe_static! {
+    pub static lazy QUEUE: Mutex<Queue> = Default::default();
+}
+
+
+/// Get the name of the Preference enabling the bypass of duplicate Messages in
+///     a Channel.
+///
+/// Input: `&str`
+/// Return: `String`
+pub fn bypass_pref(channel: &str) -> String {
+    format!("PREF_dupe_bypass_{}", channel.to_ascii_lowercase())
+}
+
+
+/// Check whether duplicate Messages should be changed to get through, in a
+///     Channel. This is off by default.
+///
+/// Input: `&str`
+/// Return: `bool`
+pub fn bypass_enabled(channel: &str) -> bool {
+    get_pref_int(&bypass_pref(channel)).unwrap_or(0) != 0
+}
+
+
+/// Send everything waiting in the queues which the Twitch limits allow.
+pub fn flush() {
+    let now = Instant::now();
+    let (senders, joins) = {
+        let mut joins = JOINS.lock();
+        (joins.senders(), joins.drain(now))
+    };
+
+    if !joins.is_empty() {
+        //  The Tab the Joins came from may have been closed since, so try any
+        //      other Tab on Twitch before giving up until the next run.
+        let sent = senders.iter().any(|tab| in_channel(tab, || for channel in &joins {
+            send_command(&format!("JOIN {}", channel));
+        }));
+
+        if !sent { JOINS.lock().restore(joins); }
+    }
+
+    let (sends, warn) = {
+        let userstate = USERSTATE.read();
+        let mut queue = QUEUE.lock();
+
+        (queue.drain(now, |ch| userstate.is_mod(ch)), queue.warn())
+    };
+
+    for (channel, command) in sends {
+        if !send_tracked(&command, || in_channel(&channel, || send_command(&command))) {
+            //  The Tab was closed while the Command waited in the queue.
+            echo(EVENT_ERR, &[format!(
+                "FAILED to send to {}, the Tab is closed: {}",
+                channel, command,
+            )], 0);
+        }
+    }
+
+    if let Some(n) = warn {
+        echo(EVENT_ERR, &[format!(
+            "Twitch rate limit reached. {} Message{} will be sent shortly.",
+            n,
+            if n == 1 { "" } else { "s" },
+        )], 0);
+    }
+}
+
+
+/// Send one Command, which is tracked in the Outbox if it is a Whisper. HexChat
+///     echoes a Whisper before `send_command()` returns, so it is tracked from
+///     just before it is sent, and no longer if it could not be sent. Tracking
+///     it only when it leaves the queue keeps a long wait from expiring it.
+///
+/// Input: `&str`, `FnOnce() -> bool`
+/// Return: `bool`
+fn send_tracked(command: &str, send: impl FnOnce() -> bool) -> bool {
+    let whisper = command.strip_prefix("SAY ").and_then(parse_command);
+
+    if let Some((target, text)) = whisper {
+        OUTBOX.lock().push(target, text, Instant::now());
+    }
+
+    let sent = send();
+
+    if let (false, Some((target, text))) = (sent, whisper) {
+        OUTBOX.lock().cancel(target, text);
+    }
+
+    sent
+}
+
+
+/// Send a Command from a Channel, as soon as the Twitch limits allow.
+///
+/// Input: `&str`, `String`
+pub fn send(channel: &str, command: String) {
+    QUEUE.lock().push(channel, command);
+    flush();
+}
+
+
+/// Normalize one Channel name, with a `#` prefix, in lowercase.
+///
+/// Input: `&str`
+/// Return: `Result<String, String>`
+fn channel_name(word: &str) -> Result<String, String> {
+    let name = word.trim_start_matches('#').to_ascii_lowercase();
+
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Err(format!("'{}' is not a valid Channel name.", word))
+    } else {
+        Ok(format!("#{}", name))
+    }
+}
+
+
+/// Break a list of Channels, separated by commas or spaces, into Channel names
+///     with a `#` prefix, in lowercase. A word of the form `@name` is replaced
+///     by the Channels of a saved group, as found by a Function. Duplicates are
+///     removed.
+///
+/// Input: `&[&str]`, `Fn(&str) -> Option<String>`
+/// Return: `Result<Vec<String>, String>`
+pub fn channel_list(
+    words: &[&str],
+    groups: impl Fn(&str) -> Option<String>,
+) -> Result<Vec<String>, String> {
+    let split = |c: char| c == ',' || c.is_whitespace();
+    let mut out: Vec<String> = Vec::new();
+
+    for word in words.iter().flat_map(|w| w.split(split)).filter(|w| !w.is_empty()) {
+        let channels: Vec<String> = match word.strip_prefix('@') {
+            Some(group) => groups(&group.to_ascii_lowercase())
+                .ok_or_else(|| format!("There is no group named '{}'.", group))?
+                .split(split)
+                .filter(|w| !w.is_empty())
+                .map(channel_name)
+                .collect::<Result<_, _>>()?,
+            None => vec![channel_name(word)?],
+        };
+
+        for channel in channels {
+            if !out.contains(&channel) { out.push(channel); }
+        }
+    }
+
+    Ok(out)
+}
+
+
+#[cfg(test)]
+mod tests_outgoing {
+    use super::*;
+
+    /// Test to confirm that a Whisper sent from the queue is tracked once, in
+    ///     the order HexChat runs things: it is sent, echoed before the send
+    ///     returns, and then reported by Twitch as failed.
+    #[test]
+    fn test_send_tracked() {
+        let fail = || OUTBOX.lock().fail(Instant::now());
+
+        assert!(send_tracked("SAY .w asdf hello", || {
+            OUTBOX.lock().echo("asdf", "hello", Instant::now());
+            true
+        }), "Whisper not sent.");
+
+        //  A Whisper typed directly by the User, only seen by its echo.
+        OUTBOX.lock().echo("qwert", "hi", Instant::now());
+
+        assert_eq!(Some((String::from("asdf"), String::from("hello"))), fail(),
+                   "Failure not matched to the queued Whisper.");
+        assert_eq!(Some((String::from("qwert"), String::from("hi"))), fail(),
+                   "Failure not matched to the typed Whisper.");
+        assert_eq!(None, fail(), "Whisper tracked twice.");
+
+        assert!(!send_tracked("SAY .w asdf lost", || false));
+        assert_eq!(None, fail(), "Unsent Whisper still tracked.");
+    }
+
+    /// Test to confirm that lists of Channels are normalized, and that groups
+    ///     are expanded.
+    #[test]
+    fn test_channel_list() {
+        let groups = |name: &str| match name {
+            "friends" => Some(String::from("#asdf, qwert")),
+            _ => None,
+        };
+
+        assert_eq!(
+            Ok(vec![
+                String::from("#zxcv"),
+                String::from("#asdf"),
+                String::from("#qwert"),
+                String::from("#uiop"),
+            ]),
+            channel_list(&["ZXCV,#asdf", "@Friends", "uiop,,"], groups),
+        );
+        assert!(channel_list(&["@enemies"], groups).is_err(), "Unknown group expanded.");
+        assert!(channel_list(&["bad-name"], groups).is_err(), "Invalid name accepted.");
+    }
+
+    /// Test to confirm that a Bucket never allows more than its limit within
+    ///     any window, while still allowing the limit over time.
+    #[test]
+    fn test_token_bucket() {
+        let t0 = Instant::now();
+        let mut bucket = TokenBucket::new(MSG_LIMIT, MSG_WINDOW, t0);
+        let mut taken: Vec<Instant> = Vec::new();
+
+        for step in 0..900 {
+            let now = t0 + Duration::from_millis(step * 100);
+
+            while bucket.take(now) { taken.push(now); }
+
+            let window = taken.iter()
+                .filter(|&&t| now.saturating_duration_since(t) < MSG_WINDOW)
+                .count();
+            assert!(window as u32 <= MSG_LIMIT, "Limit exceeded: {} in window.", window);
+        }
+
+        assert!(taken.len() as u32 >= MSG_LIMIT * 3 / 2, "Bucket refilled too slowly.");
+        assert_eq!(MSG_LIMIT / 2, taken.iter().filter(|&&t| t == t0).count() as u32,
+                   "Wrong size of first burst.");
+    }
+
+    /// Test to confirm that Joins are released gradually, and only once.
+    #[test]
+    fn test_join_queue() {
+        let mut queue = JoinQueue::default();
+        let t0 = Instant::now();
+
+        for n in 0..JOIN_LIMIT + 5 {
+            assert!(queue.push("#origin", format!("#ch{}", n)));
+        }
+        assert!(!queue.push("#origin", String::from("#ch0")), "Duplicate queued.");
+
+        let first = queue.drain(t0);
+        assert_eq!(JOIN_LIMIT as usize / 2, first.len(), "Wrong size of first burst.");
+        assert_eq!("#ch0", first[0], "Joins released out of order.");
+        assert!(queue.drain(t0).is_empty(), "Joins released early.");
+        assert_eq!(10, queue.drain(t0 + JOIN_WINDOW * 2).len(), "Joins not released.");
+        assert_eq!(5, queue.drain(t0 + JOIN_WINDOW * 4).len(), "Joins not released.");
+        assert_eq!(0, queue.len());
+        assert_eq!(vec!["#origin"], queue.senders());
+
+        queue.joined("#CH0");
+        assert!(!queue.push("#origin", String::from("#ch0")), "Joined Channel queued.");
+        queue.left(Some("#ch0"));
+        assert!(queue.push("#origin", String::from("#ch0")), "Left Channel not queued.");
+
+        queue.restore(vec![String::from("#ch1"), String::from("#ch0")]);
+        assert_eq!(2, queue.len(), "Restored Channel queued twice.");
+        assert_eq!(vec!["#ch1", "#ch0"], queue.drain(t0 + JOIN_WINDOW * 6),
+                   "Restored Channels out of order.");
+    }
+
+    /// Test to confirm that repeated Messages alternate with and without the
+    ///     suffix, and only within the window.
+    #[test]
+    fn test_duplicates() {
+        let mut dupes = Duplicates::default();
+        let t0 = Instant::now();
+        let suffixed = format!("hello{}", DUPE_SUFFIX);
+
+        assert_eq!(None, dupes.bypass("#zxcv", "hello", t0), "First Message changed.");
+        assert_eq!(Some(suffixed.clone()), dupes.bypass("#ZXCV", "hello", t0),
+                   "Duplicate not changed.");
+        assert_eq!(None, dupes.bypass("#zxcv", "hello", t0), "Third Message changed.");
+        assert_eq!(Some(suffixed), dupes.bypass("#zxcv", "hello", t0),
+                   "Fourth Message not changed.");
+        assert_eq!(None, dupes.bypass("#other", "hello", t0), "Channels not separate.");
+        assert_eq!(None, dupes.bypass("#other", "hello", t0 + DUPE_WINDOW),
+                   "Message changed after window.");
+    }
+
+    /// Test to confirm that normal Messages spread across many Channels stay
+    ///     within the account limit, in every window.
+    #[test]
+    fn test_queue_global() {
+        let mut queue = Queue::default();
+        let t0 = Instant::now();
+        let mut sent: Vec<Instant> = Vec::new();
+
+        for n in 0..MSG_LIMIT * 5 {
+            queue.push(&format!("#ch{}", n % 5), format!("SAY {}", n));
+        }
+
+        for step in 0..1200 {
+            let now = t0 + Duration::from_millis(step * 100);
+
+            for _ in queue.drain(now, |_| false) { sent.push(now); }
+
+            let window = sent.iter()
+                .filter(|&&t| now.saturating_duration_since(t) < MSG_WINDOW)
+                .count();
+            assert!(window as u32 <= MSG_LIMIT, "Account limit exceeded: {} in window.", window);
+        }
+
+        assert_eq!(MSG_LIMIT as usize / 2, sent.iter().filter(|&&t| t == t0).count(),
+                   "Wrong size of first burst across Channels.");
+        assert!(sent.len() as u32 >= MSG_LIMIT * 2, "Messages not sent over time.");
+    }
+
+    /// Test to confirm that typed text is taken whole from the words of the
+    ///     input hook, with its spacing, and that Commands are left alone.
+    #[test]
+    fn test_input() {
+        let mut dupes = Duplicates::default();
+        let t0 = Instant::now();
+        let words = |text: &str| -> Vec<String> {
+            let mut out: Vec<String> = text.split(' ').map(String::from).collect();
+            out.resize(32, String::new());
+            out
+        };
+
+        assert_eq!(None, dupes.input("#zxcv", &words("hello"), t0));
+        assert_eq!(Some(format!("hello{}", DUPE_SUFFIX)),
+                   dupes.input("#zxcv", &words("hello"), t0),
+                   "One word Message not changed.");
+
+        assert_eq!(None, dupes.input("#zxcv", &words("hello  big world"), t0));
+        assert_eq!(Some(format!("hello  big world{}", DUPE_SUFFIX)),
+                   dupes.input("#zxcv", &words("hello  big world"), t0),
+                   "Words or spacing lost.");
+
+        assert_eq!(None, dupes.input("#zxcv", &words(".me waves"), t0));
+        assert_eq!(None, dupes.input("#zxcv", &words(".me waves"), t0),
+                   "Command changed.");
+        assert_eq!(None, dupes.input("#zxcv", &words(""), t0), "Empty input changed.");
+    }
+
+    /// Test to confirm that Messages are limited per Channel, by Moderator
+    ///     status, and kept in order.
+    #[test]
+    fn test_queue() {
+        let mut queue = Queue::default();
+        let t0 = Instant::now();
+        let is_mod = |ch: &str| ch == "#modded";
+
+        for n in 0..MSG_LIMIT {
+            queue.push("#normal", format!("SAY {}", n));
+            queue.push("#MODDED", format!("SAY {}", n));
+        }
+
+        let sent = queue.drain(t0, is_mod);
+        let normal: Vec<&str> = sent.iter()
+            .filter(|(ch, _)| ch == "#normal")
+            .map(|(_, cmd)| cmd.as_str())
+            .collect();
+
+        assert_eq!(MSG_LIMIT as usize / 2, normal.len(), "Normal Channel not limited.");
+        assert_eq!("SAY 0", normal[0], "Messages sent out of order.");
+        assert_eq!(MSG_LIMIT as usize, sent.len() - normal.len(), "Moderator limit not used.");
+        assert_eq!(Some(MSG_LIMIT as usize / 2), queue.warn(), "Throttling not warned.");
+        assert_eq!(None, queue.warn(), "Throttling warned twice.");
+
+        assert_eq!(MSG_LIMIT as usize / 2, queue.drain(t0 + MSG_WINDOW, is_mod).len());
+        assert_eq!(None, queue.warn(), "Warned with an empty queue.");
+    }
+}
@@ -2,10 +2,26 @@ mod printing;
 mod tabs;
 
 
-use hexchat::{EatMode, PrintEvent, send_command};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Instant,
+};
+
+use hexchat::{
+    EatMode,
+    get_channel,
+    get_current_channel,
+    get_pref_int,
+    print_event_to_channel,
+    PrintEvent,
+    send_command,
+    set_current_channel,
+};
 
 pub use printing::{
+    Badges,
     echo,
+    echo_at,
     EVENT_ALERT,
     EVENT_CHANNEL,
     EVENT_ERR,
@@ -13,8 +29,60 @@ pub use printing::{
     EVENT_REWARD,
     USERSTATE,
 };
-pub use tabs::TABCOLORS;
-use super::{events, irc::Message};
+pub use tabs::{Category, TABCOLORS};
+use super::{
+    cheermotes::{self, Mode},
+    events,
+    filters::{HIGHLIGHTS, HIGHLIGHTS_TAB, IGNORES},
+    irc::Message,
+    profiles::PROFILES,
+    raids::RAIDS,
+    users::USERS,
+    whispers,
+};
+
+
+/// Run a Function in the context of a Channel on the Twitch Network, and then
+///     switch back to the previous context. Returns `false`, without running
+///     the Function, if the Channel cannot be found.
+///
+/// Input: `&str`, `FnOnce()`
+/// Return: `bool`
+pub fn in_channel(channel: &str, func: impl FnOnce()) -> bool {
+    match get_channel("Twitch", channel) {
+        Some(target) => {
+            let prev = get_current_channel();
+
+            if set_current_channel(&target) {
+                func();
+                set_current_channel(&prev);
+                true
+            } else { false }
+        }
+        None => false,
+    }
+}
+
+
+/// Whether a topic is being set by the Plugin, and should not be announced.
+static QUIET_TOPIC: AtomicBool = AtomicBool::new(false);
+
+
+/// Set the topic of a Channel, in its Tab, without announcing the change. This
+///     must be run in the context of the Channel.
+///
+/// Input: `&str`, `&str`
+pub fn set_topic(channel: &str, topic: &str) {
+    QUIET_TOPIC.store(true, Ordering::SeqCst);
+    send_command(&format!("RECV :Twitch@twitch.tv TOPIC {} :{}", channel, topic));
+    QUIET_TOPIC.store(false, Ordering::SeqCst);
+}
+
+
+/// Check whether a topic change is being made quietly by `set_topic()`.
+///
+/// Return: `bool`
+pub fn is_quiet_topic() -> bool { QUIET_TOPIC.load(Ordering::SeqCst) }
 
 
 /// Message comes from Server. IRC Representation available.
@@ -27,23 +95,25 @@ pub fn print_with_irc(
     if msg.tags.is_some() {
         if let Some(bits) = msg.get_tag("bits") {
             if let Ok(n) = bits.parse::<usize>() {
-                events::cheer(msg.author(), n);
+                events::cheer(channel, msg.author(), n);
             }
         }
 
         if let Some(eat) = events::reward(word, &msg) { return eat; }
     }
 
+    let time = msg.time();
+
     match etype {
         PrintEvent::YOUR_MESSAGE
         | PrintEvent::YOUR_ACTION
         => {
-            echo(etype, &[
+            echo_at(etype, &time, &[
                 &*word[0],
                 &*word[1],
                 "_",
                 USERSTATE.read().get(&channel),
-            ], 2);
+            ], Category::Chat.color());
 
             EatMode::All
         }
@@ -52,23 +122,78 @@ pub fn print_with_irc(
         | PrintEvent::CHANNEL_MSG_HILIGHT
         | PrintEvent::CHANNEL_ACTION_HILIGHT
         => {
+            //  A Message matching an Ignore Rule is not printed at all, but its
+            //      author is still recorded.
+            if IGNORES.write().check(channel, &msg, &word[1]) {
+                PROFILES.write().message(channel, &msg);
+                return EatMode::All;
+            }
+
             let badges = printing::Badges::from_str(
-                &msg.get_tag("badges").unwrap_or_default(),
+                &printing::with_markers(&msg),
                 &msg.get_tag("badge-info").unwrap_or_default(),
             );
-            echo(
+
+            if msg.get_tag("first-msg").as_deref() == Some("1")
+                && get_pref_int("PREF_first_alert").unwrap_or(0) != 0
+            {
+                echo_at(EVENT_ALERT, &time, &[
+                    "FIRST MESSAGE",
+                    &format!(
+                        "{} is chatting here for the first time",
+                        msg.get_tag("display-name")
+                            .filter(|name| !name.is_empty())
+                            .as_deref()
+                            .unwrap_or_else(|| msg.author()),
+                    ),
+                ], Category::Highlight.color());
+            }
+            let text: String = msg.get_tag("bits")
+                .and_then(|bits| bits.parse().ok())
+                .and_then(|bits| cheermotes::render(&word[1], bits, Mode::current()))
+                .unwrap_or_else(|| word[1].to_owned());
+            let text: String = match printing::lag_marker(&time) {
+                Some(marker) => format!("{} {}", text, marker),
+                None => text,
+            };
+
+            //  A Message matching a Highlight Rule is promoted to a Highlight.
+            let rule = HIGHLIGHTS.read().find(channel, &msg, &word[1]).cloned();
+            let etype = match (etype, &rule) {
+                (PrintEvent::CHANNEL_MESSAGE, Some(_)) => PrintEvent::CHANNEL_MSG_HILIGHT,
+                (PrintEvent::CHANNEL_ACTION, Some(_)) => PrintEvent::CHANNEL_ACTION_HILIGHT,
+                _ => etype,
+            };
+
+            echo_at(
                 etype,
-                &[&*word[0], &*word[1], "", &*badges.output],
-                if etype == PrintEvent::CHANNEL_MSG_HILIGHT
-                    || etype == PrintEvent::CHANNEL_ACTION_HILIGHT
-                { 3 } else { 2 },
+                &time,
+                &[&*word[0], &*text, "", &*badges.output],
+                rule.as_ref().and_then(|r| r.color).unwrap_or_else(|| {
+                    if etype == PrintEvent::CHANNEL_MSG_HILIGHT
+                        || etype == PrintEvent::CHANNEL_ACTION_HILIGHT
+                    { Category::Highlight } else { Category::Chat }.color()
+                }),
             );
 
-            send_command(&format!(
-                "RECV :{0}!{0}@twitch.tv/{0} JOIN {1}",
-                msg.author().to_ascii_lowercase(),
-                channel,
-            ));
+            if rule.is_some_and(|r| r.copy) {
+                print_event_to_channel(&events::ensure_tab(HIGHLIGHTS_TAB), etype, &[
+                    &*word[0],
+                    &format!("\x0314[{}]\x0F {}", channel, text),
+                    "",
+                    &*badges.output,
+                ]);
+            }
+
+            PROFILES.write().message(channel, &msg);
+
+            //  Note that the User is active. If they are not in the User List
+            //      yet, they will be Joined by the next Timer flush, and may be
+            //      counted as arriving with a recent Raid.
+            let now = Instant::now();
+            let new = USERS.write().seen(channel, msg.author(), now);
+
+            if new { RAIDS.write().arrived(channel, msg.author(), now); }
 
             EatMode::All
         }
@@ -79,10 +204,16 @@ pub fn print_with_irc(
 
 /// No IRC Representation available for Message.
 pub fn print_without_irc(channel: &str, etype: PrintEvent, word: &[String]) -> EatMode {
-    if word[1].starts_with(".w ") || !channel.starts_with::<&[char]>(&['#', '&']) {
-        //  User has spoken inside a Whisper Tab, or executed `.w` elsewhere.
-        //      We must take the message typed, and forward it to the Whisper
-        //      Handler.
+    if let Some((user, text)) = whispers::parse_command(&word[1]) {
+        //  User has executed `.w` or `/w`, or this is the echo of a Whisper
+        //      sent by the Plugin. It has already been sent, so it only needs
+        //      to be printed in the Whisper Tab.
+        events::whisper_echo(etype, &channel, word, user, text);
+
+        EatMode::All
+    } else if !channel.starts_with::<&[char]>(&['#', '&']) {
+        //  User has spoken inside a Whisper Tab. We must take the message
+        //      typed, and send it as a Whisper.
         events::whisper_send(etype, &channel, word);
 
         EatMode::All
@@ -98,7 +229,7 @@ pub fn print_without_irc(channel: &str, etype: PrintEvent, word: &[String]) -> E
             &*word[1],
             "_",
             USERSTATE.read().get(&channel),
-        ], 2);
+        ], Category::Chat.color());
 
         EatMode::All
     } else {
@@ -1,10 +1,11 @@
 use std::collections::HashMap;
 
-use hexchat::{print_event, PrintEvent};
+use chrono::{DateTime, Utc};
+use hexchat::{get_pref_int, print_event, print_event_at, PrintEvent};
 use parking_lot::RwLock;
 
 use super::{
-    super::irc::split_at_char,
+    super::irc::{Message, split_at_char},
     tabs::TABCOLORS,
 };
 
@@ -31,9 +32,45 @@ pub fn echo(event: PrintEvent, args: &[impl AsRef<str>], tab_color: u8) {
 }
 
 
+/// Echo At: Print an event to HexChat in the current Channel with a specific
+///     Server Time, and color the tab.
+///
+/// Input: `PrintEvent`, `&DateTime<Utc>`, `&[impl AsRef<str>]`, `u8`
+#[inline]
+pub fn echo_at(
+    event: PrintEvent,
+    time: &DateTime<Utc>,
+    args: &[impl AsRef<str>],
+    tab_color: u8,
+) {
+    print_event_at(event, time, args);
+    TABCOLORS.write().color(tab_color);
+}
+
+
+/// Lag Marker: If the User has set a number of seconds in `PREF_lag_mark`, and
+///     a Message sent at the given time arrives more than that many seconds
+///     late, return a marker showing the delay.
+///
+/// Input: `&DateTime<Utc>`
+/// Return: `Option<String>`
+pub fn lag_marker(sent: &DateTime<Utc>) -> Option<String> {
+    let limit = get_pref_int("PREF_lag_mark").filter(|&n| n > 0)?;
+    let lag = Utc::now().signed_duration_since(*sent).num_seconds();
+
+    if lag > limit as i64 {
+        Some(format!("\x0314[+{}s]\x0F", lag))
+    } else { None }
+}
+
+
 /// BADGE_NONE: A placeholder Badge string for the User when a UserState has not
 ///     been received.
 const BADGE_NONE: &str = "_ ";
+/// MARKERS: Tags which describe a Message, rather than its author, but which
+///     are shown as Badges when their value is `1`. Their characters are found
+///     among those of the Badges, by the names of the Tags.
+static MARKERS: &[&str] = &["first-msg", "returning-chatter"];
 /// BITS: Badge characters for Bits. If a User has a Bits Badge, the User is
 ///     given the `char` corresponding to the last value found here which is
 ///     LESS THAN OR EQUAL TO the Rank of the Badge.
@@ -125,6 +162,9 @@ fn get_badge(class: &str, rank: &str) -> char {
 
         "glhf-pledge"       /**/ => '~',
 
+        "first-msg"         /**/ => '✱',
+        "returning-chatter" /**/ => '↻',
+
         s if s.starts_with("twitchcon") => 'c',
         _ => '?',
     }
@@ -146,6 +186,25 @@ fn highest(max: usize, seq: &[(usize, char)]) -> char {
 }
 
 
+/// Get the Badges Tag of a Message, with any Markers set on the Message placed
+///     before the Badges, as if they were Badges themselves.
+///
+/// Input: `&Message`
+/// Return: `String`
+pub fn with_markers(msg: &Message) -> String {
+    let mut out: Vec<String> = MARKERS.iter()
+        .filter(|&&tag| msg.get_tag(tag).as_deref() == Some("1"))
+        .map(|tag| format!("{}/1", tag))
+        .collect();
+
+    if let Some(badges) = msg.get_tag("badges").filter(|b| !b.is_empty()) {
+        out.push(badges);
+    }
+
+    out.join(",")
+}
+
+
 /// Badges: A Struct storing the Input and Output of the process of breaking
 ///     down a badge value. This effectively serves the purpose of a Cached
 ///     Function.
@@ -192,6 +251,14 @@ impl Badges {
 
         Self { input, output }
     }
+
+    /// Check whether a Badge of a given class is among the original input.
+    ///
+    /// Input: `&str`
+    /// Return: `bool`
+    pub fn has(&self, class: &str) -> bool {
+        self.input.split(',').any(|pair| split_at_char(pair, '/').0 == class)
+    }
 }
 
 
@@ -212,6 +279,25 @@ impl States {
         }
     }
 
+    /// Check whether the User is a Moderator, or the Broadcaster, in a given
+    ///     Channel, as shown by their Badges.
+    ///
+    /// Input: `&str`
+    /// Return: `bool`
+    pub fn is_mod(&self, channel: &str) -> bool {
+        match self.inner.get(channel) {
+            Some(badges) => badges.has("moderator") || badges.has("broadcaster"),
+            None => false,
+        }
+    }
+
+    /// Iterate over the Channels for which a User State has been received.
+    ///
+    /// Return: `impl Iterator<Item = &str>`
+    pub fn channels(&self) -> impl Iterator<Item = &str> {
+        self.inner.keys().map(String::as_str)
+    }
+
     /// Set the Badges for the User in a given Channel. This is mostly just a
     ///     guarded passthrough to the `HashMap::insert()` of the internal map,
     ///     but with one significant difference: If the current value for the
@@ -238,3 +324,25 @@ impl States {
 safe_static! {
     pub static lazy USERSTATE: RwLock<States> = Default::default();
 }
+
+
+#[cfg(test)]
+mod tests_printing {
+    use super::*;
+
+    /// Test to confirm that Markers are shown before Badges, only when set.
+    #[test]
+    fn test_markers() {
+        let msg: Message = r"@badges=vip/1;first-msg=1;returning-chatter=0 :asdf!asdf@asdf.tmi.twitch.tv PRIVMSG #zxcv :hi"
+            .parse().expect("Failed to parse sample.");
+        let badges = Badges::from_str(&with_markers(&msg), "");
+
+        assert_eq!("first-msg/1,vip/1", with_markers(&msg));
+        assert_eq!("✱⚑ ", badges.output, "Markers not shown as Badges.");
+        assert!(badges.has("vip"), "Badge lost among Markers.");
+
+        let plain: Message = r"@badges= :asdf!asdf@asdf.tmi.twitch.tv PRIVMSG #zxcv :hi"
+            .parse().expect("Failed to parse sample.");
+        assert_eq!("", with_markers(&plain), "Marker added without Tag.");
+    }
+}
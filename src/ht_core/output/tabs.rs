@@ -1,38 +1,242 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
-use hexchat::{get_channel_name, get_current_channel, get_focused_channel, send_command};
+use hexchat::{
+    get_channel_name,
+    get_current_channel,
+    get_focused_channel,
+    get_network_name,
+    get_pref_int,
+    send_command,
+};
 use parking_lot::RwLock;
 
 
-/// Tabs: A mapping of HexChat Channel names to their current colors. Provides
-///     an interface to change the colors, while also minimizing unnecessary
-///     calls to HexChat Commands.
+/// Category: A kind of Event which may change the color of a Tab. The color
+///     used for each Category can be set by the User, both globally and for
+///     specific Channels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Category {
+    Chat,
+    Highlight,
+    Sub,
+    Cheer,
+    BigCheer,
+    Raid,
+    Moderation,
+    Whisper,
+    Reward,
+    Stream,
+}
+
+impl Category {
+    pub const ALL: &'static [Self] = &[
+        Self::Chat,
+        Self::Highlight,
+        Self::Sub,
+        Self::Cheer,
+        Self::BigCheer,
+        Self::Raid,
+        Self::Moderation,
+        Self::Whisper,
+        Self::Reward,
+        Self::Stream,
+    ];
+
+    /// Find the Category with a given name.
+    ///
+    /// Input: `&str`
+    /// Return: `Option<Category>`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|cat| name.eq_ignore_ascii_case(cat.name()))
+    }
+
+    /// Return: `&str`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Chat => "chat",
+            Self::Highlight => "highlight",
+            Self::Sub => "sub",
+            Self::Cheer => "cheer",
+            Self::BigCheer => "bigcheer",
+            Self::Raid => "raid",
+            Self::Moderation => "moderation",
+            Self::Whisper => "whisper",
+            Self::Reward => "reward",
+            Self::Stream => "stream",
+        }
+    }
+
+    /// The color used for this Category if the User has not set one.
+    ///
+    /// Return: `u8`
+    pub fn default_color(self) -> u8 {
+        match self {
+            Self::Highlight | Self::BigCheer => 3,
+            Self::Chat | Self::Sub | Self::Whisper | Self::Reward | Self::Stream => 2,
+            Self::Cheer | Self::Raid | Self::Moderation => 1,
+        }
+    }
+
+    /// Get the name of the Preference storing the color for this Category. If
+    ///     a Channel is provided, the name is for the override in that Channel.
+    ///
+    /// Input: `Option<&str>`
+    /// Return: `String`
+    pub fn pref(self, channel: Option<&str>) -> String {
+        match channel {
+            Some(ch) => format!("PREF_color_{}_{}", self.name(), ch.to_ascii_lowercase()),
+            None => format!("PREF_color_{}", self.name()),
+        }
+    }
+
+    /// Find the color for this Category in a Channel. An override for the
+    ///     Channel is used first, then the global setting, then the default.
+    ///
+    /// Input: `&str`
+    /// Return: `u8`
+    pub fn color_in(self, channel: &str) -> u8 {
+        self.color_in_or(channel, self.default_color())
+    }
+
+    /// Find the color for this Category in a Channel, as with `color_in()`,
+    ///     but with a different default. Some Events are quieter than others
+    ///     in their Category, unless the User sets a color for it.
+    ///
+    /// Input: `&str`, `u8`
+    /// Return: `u8`
+    pub fn color_in_or(self, channel: &str, default: u8) -> u8 {
+        get_pref_int(&self.pref(Some(channel)))
+            .or_else(|| get_pref_int(&self.pref(None)))
+            .map_or(default, |c| c.clamp(0, 3) as u8)
+    }
+
+    /// Find the color for this Category in the current Channel.
+    ///
+    /// Return: `u8`
+    pub fn color(self) -> u8 { self.color_in(&get_channel_name()) }
+
+    /// Find the color for this Category in the current Channel, with a
+    ///     different default.
+    ///
+    /// Input: `u8`
+    /// Return: `u8`
+    pub fn color_or(self, default: u8) -> u8 {
+        self.color_in_or(&get_channel_name(), default)
+    }
+}
+
+
+/// TabKey: The identity of a Tab, as the Network and Channel it belongs to.
+///     Both names are stored in lowercase, so that they compare the same way
+///     HexChat compares them.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct TabKey {
+    network: String,
+    channel: String,
+}
+
+impl TabKey {
+    /// Input: `&str`, `&str`
+    /// Return: `TabKey`
+    pub fn new(network: &str, channel: &str) -> Self {
+        Self {
+            network: network.to_lowercase(),
+            channel: channel.to_lowercase(),
+        }
+    }
+
+    /// Get the Key of the current HexChat context.
+    ///
+    /// Return: `TabKey`
+    pub fn current() -> Self {
+        Self::new(&get_network_name().unwrap_or_default(), &get_channel_name())
+    }
+}
+
+impl fmt::Display for TabKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.channel, self.network)
+    }
+}
+
+
+/// Tabs: A mapping of HexChat Tabs to their current colors. Provides an
+///     interface to change the colors, while also minimizing unnecessary calls
+///     to HexChat Commands. Tabs without a color are not stored.
 #[derive(Default)]
-pub struct Tabs { inner: HashMap<String, u8> }
+pub struct Tabs { inner: HashMap<TabKey, u8> }
 
 impl Tabs {
+    /// Raise the color of a Tab. The color is only changed if the new color is
+    ///     higher than the current one. Returns `true` if it was changed.
+    ///
+    /// Input: `TabKey`, `u8`
+    /// Return: `bool`
+    pub fn raise(&mut self, key: TabKey, color_new: u8) -> bool {
+        if color_new > self.get(&key) {
+            // New color is greater than old color. Replace.
+            self.inner.insert(key, color_new);
+            true
+        } else { false }
+    }
+
+    /// Get the current color of a Tab.
+    ///
+    /// Input: `&TabKey`
+    /// Return: `u8`
+    pub fn get(&self, key: &TabKey) -> u8 {
+        self.inner.get(key).copied().unwrap_or(0)
+    }
+
+    /// Forget the color of a Tab. Returns `true` if the Tab had a color.
+    ///
+    /// Input: `&TabKey`
+    /// Return: `bool`
+    pub fn remove(&mut self, key: &TabKey) -> bool {
+        matches!(self.inner.remove(key), Some(color) if color > 0)
+    }
+
+    /// List all Tabs on a Network which currently have a color, with the
+    ///     colors.
+    ///
+    /// Input: `&str`
+    /// Return: `Vec<(TabKey, u8)>`
+    pub fn unread(&self, network: &str) -> Vec<(TabKey, u8)> {
+        let network = network.to_lowercase();
+        let mut out: Vec<(TabKey, u8)> = self.inner.iter()
+            .filter(|(key, &color)| color > 0 && key.network == network)
+            .map(|(key, &color)| (key.clone(), color))
+            .collect();
+
+        out.sort_unstable();
+        out
+    }
+
     /// Check for the current Channel in the Map of colors. If the Channel is
     ///     not focused AND the provided new color is higher than the current
     ///     one, the Map is updated and the `GUI COLOR` Command is run.
     ///
     /// Input: `u8`
     pub fn color(&mut self, color_new: u8) {
-        if !get_focused_channel().contains(&get_current_channel()) {
-            let name = get_channel_name();
-
-            if &color_new > self.inner.get(&name).unwrap_or(&0) {
-                // New color is greater than old color. Replace.
-                self.inner.insert(name, color_new);
-                send_command(&format!("GUI COLOR {}", color_new));
-            }
+        if !get_focused_channel().contains(&get_current_channel())
+            && self.raise(TabKey::current(), color_new)
+        {
+            send_command(&format!("GUI COLOR {}", color_new));
         }
     }
 
-    /// Set the color of the current Channel to 0. Done when a Channel becomes
+    /// Clear the color of the current Channel. Done when a Channel becomes
     ///     focused, so that its unread status is cleared.
     pub fn reset(&mut self) {
-        self.inner.insert(get_channel_name(), 0);
-        send_command("GUI COLOR 0");
+        if self.remove(&TabKey::current()) {
+            send_command("GUI COLOR 0");
+        }
+    }
+
+    /// Forget the current Channel. Done when a Channel is closed, so that a
+    ///     new Tab with the same name does not inherit its color.
+    pub fn close(&mut self) {
+        self.remove(&TabKey::current());
     }
 }
 
@@ -40,3 +244,82 @@ impl Tabs {
 safe_static! {
     pub static lazy TABCOLORS: RwLock<Tabs> = Default::default();
 }
+
+
+#[cfg(test)]
+mod tests_tabs {
+    use super::*;
+
+    /// Test to confirm that colors only ever increase until they are cleared.
+    #[test]
+    fn test_raise() {
+        let mut tabs = Tabs::default();
+        let key = TabKey::new("Twitch", "#asdf");
+
+        assert!(tabs.raise(key.clone(), 1), "Initial color is not set.");
+        assert!(tabs.raise(key.clone(), 3), "Higher color is not set.");
+        assert!(!tabs.raise(key.clone(), 2), "Lower color replaces higher.");
+        assert_eq!(3, tabs.get(&key), "Tab has the wrong color.");
+
+        assert!(tabs.remove(&key), "Removal of colored Tab returns false.");
+        assert_eq!(0, tabs.get(&key), "Tab color is not cleared.");
+        assert!(!tabs.remove(&key), "Removal of cleared Tab returns true.");
+        assert!(tabs.raise(key, 1), "Color is not set after clearing.");
+    }
+
+    /// Test to confirm that Channels of the same name on different Networks
+    ///     are kept separate, and that case is ignored.
+    #[test]
+    fn test_networks() {
+        let mut tabs = Tabs::default();
+        let twitch = TabKey::new("Twitch", "#asdf");
+        let other = TabKey::new("Libera", "#asdf");
+
+        tabs.raise(twitch.clone(), 2);
+        assert_eq!(0, tabs.get(&other), "Color leaks across Networks.");
+
+        tabs.raise(other.clone(), 1);
+        tabs.remove(&other);
+        assert_eq!(2, tabs.get(&twitch), "Removal affects other Networks.");
+
+        assert_eq!(
+            2,
+            tabs.get(&TabKey::new("twitch", "#ASDF")),
+            "Keys are case sensitive.",
+        );
+    }
+
+    /// Test to confirm that only colored Tabs are listed as unread, in order.
+    #[test]
+    fn test_unread() {
+        let mut tabs = Tabs::default();
+        let a = TabKey::new("Twitch", "#aaa");
+        let b = TabKey::new("Twitch", "#bbb");
+        let c = TabKey::new("Twitch", "#ccc");
+
+        tabs.raise(b.clone(), 1);
+        tabs.raise(a.clone(), 3);
+        tabs.raise(c.clone(), 0);
+
+        assert_eq!(
+            vec![(a, 3), (b, 1)],
+            tabs.unread("Twitch"),
+            "Unread Tabs are listed incorrectly.",
+        );
+    }
+
+    /// Test to confirm that only Tabs on the given Network are listed as
+    ///     unread.
+    #[test]
+    fn test_unread_networks() {
+        let mut tabs = Tabs::default();
+        let twitch = TabKey::new("Twitch", "#asdf");
+        let other = TabKey::new("Libera", "#asdf");
+
+        tabs.raise(twitch.clone(), 2);
+        tabs.raise(other.clone(), 3);
+
+        assert_eq!(vec![(twitch, 2)], tabs.unread("twitch"), "Other Network listed.");
+        assert_eq!(vec![(other, 3)], tabs.unread("Libera"), "Network not listed.");
+    }
+}
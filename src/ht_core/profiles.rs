@@ -0,0 +1,151 @@
+//! Module for what is known locally about each User: how they appear, what
+//!     Badges they have, how much they have said, and what Moderators have
+//!     done to them, in each Channel, during this session.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+
+use super::irc::Message;
+
+
+/// Action: A Timeout or Ban of a User.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Action {
+    pub time: DateTime<Utc>,
+    /// The length of a Timeout in seconds, or `None` for a permanent Ban.
+    pub duration: Option<u64>,
+    pub reason: String,
+}
+
+
+/// Presence: What is known about a User in one Channel.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Presence {
+    pub badges: String,
+    pub badge_info: String,
+    pub messages: usize,
+    pub deleted: usize,
+    pub actions: Vec<Action>,
+    pub last_seen: Option<DateTime<Utc>>,
+}
+
+
+/// Profile: What is known about a User, in all Channels.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    pub display: String,
+    pub color: String,
+    pub user_id: String,
+    pub channels: HashMap<String, Presence>,
+}
+
+impl Profile {
+    /// Get the Channels in which the User is known, sorted by name.
+    ///
+    /// Return: `Vec<(&String, &Presence)>`
+    pub fn sorted(&self) -> Vec<(&String, &Presence)> {
+        let mut channels: Vec<_> = self.channels.iter().collect();
+        channels.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        channels
+    }
+}
+
+
+/// Profiles: A mapping of login names to what is known about each User.
+#[derive(Default)]
+pub struct Profiles { inner: HashMap<String, Profile> }
+
+impl Profiles {
+    /// Input: `&str`, `&str`
+    /// Return: `&mut Presence`
+    fn presence(&mut self, user: &str, channel: &str) -> &mut Presence {
+        self.inner.entry(user.to_ascii_lowercase()).or_default()
+            .channels.entry(channel.to_ascii_lowercase()).or_default()
+    }
+
+    /// Record a Message from a User, taking their appearance from its Tags.
+    ///
+    /// Input: `&str`, `&Message`
+    pub fn message(&mut self, channel: &str, msg: &Message) {
+        let user = msg.author().to_ascii_lowercase();
+        let profile = self.inner.entry(user.clone()).or_default();
+
+        if let Some(display) = msg.get_tag("display-name").filter(|s| !s.is_empty()) {
+            profile.display = display;
+        }
+        if let Some(color) = msg.get_tag("color") { profile.color = color; }
+        if let Some(id) = msg.get_tag("user-id") { profile.user_id = id; }
+
+        let presence = self.presence(&user, channel);
+
+        presence.badges = msg.get_tag("badges").unwrap_or_default();
+        presence.badge_info = msg.get_tag("badge-info").unwrap_or_default();
+        presence.messages += 1;
+        presence.last_seen = Some(msg.time());
+    }
+
+    /// Record a Timeout or Ban of a User.
+    ///
+    /// Input: `&str`, `&str`, `Action`
+    pub fn action(&mut self, channel: &str, user: &str, action: Action) {
+        self.presence(user, channel).actions.push(action);
+    }
+
+    /// Record that a Message of a User was deleted.
+    ///
+    /// Input: `&str`, `&str`
+    pub fn deleted(&mut self, channel: &str, user: &str) {
+        self.presence(user, channel).deleted += 1;
+    }
+
+    /// Input: `&str`
+    /// Return: `Option<&Profile>`
+    pub fn get(&self, user: &str) -> Option<&Profile> {
+        self.inner.get(&user.to_ascii_lowercase())
+    }
+}
+
+
+safe_static! {
+    pub static lazy PROFILES: RwLock<Profiles> = Default::default();
+}
+
+
+#[cfg(test)]
+mod tests_profiles {
+    use super::*;
+
+    /// Test to confirm that Messages and Moderator actions are counted in the
+    ///     right Channels.
+    #[test]
+    fn test_profiles() {
+        let mut profiles = Profiles::default();
+        let msg: Message = r"@badge-info=subscriber/8;badges=subscriber/6;color=#FF0000;display-name=AsdfQwert;user-id=123 :asdfqwert!asdfqwert@asdfqwert.tmi.twitch.tv PRIVMSG #zxcv :hello"
+            .parse().expect("Failed to parse sample.");
+
+        profiles.message("#zxcv", &msg);
+        profiles.message("#ZXCV", &msg);
+        profiles.message("#other", &msg);
+        profiles.deleted("#zxcv", "AsdfQwert");
+        profiles.action("#zxcv", "asdfqwert", Action {
+            time: Utc::now(),
+            duration: Some(600),
+            reason: String::new(),
+        });
+
+        let profile = profiles.get("ASDFQWERT").expect("Profile not recorded.");
+        let zxcv = &profile.channels["#zxcv"];
+
+        assert_eq!("AsdfQwert", profile.display);
+        assert_eq!("#FF0000", profile.color);
+        assert_eq!("123", profile.user_id);
+        assert_eq!(2, zxcv.messages, "Messages counted wrong.");
+        assert_eq!(1, zxcv.deleted, "Deletions counted wrong.");
+        assert_eq!(Some(600), zxcv.actions[0].duration);
+        assert_eq!("subscriber/6", zxcv.badges);
+        assert_eq!(vec!["#other", "#zxcv"], profile.sorted().iter()
+            .map(|(ch, _)| ch.as_str()).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,116 @@
+//! Module for following up on Raids. The Users who start chatting in a Channel
+//!     shortly after it is Raided are remembered, so that they can be listed.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use parking_lot::RwLock;
+
+
+/// Default number of minutes after a Raid in which new chatters are listed.
+pub const RAIDERS_DEFAULT: u64 = 5;
+/// Time after a Raid for which new chatters are recorded at all.
+const TRACK_LIMIT: Duration = Duration::from_secs(30 * 60);
+
+
+/// Raid: The most recent Raid on a Channel, and the Users who have started to
+///     chat since, with how long after the Raid each of them first spoke.
+pub struct Raid {
+    pub raider: String,
+    pub viewers: usize,
+    time: Instant,
+    chatters: Vec<(String, Duration)>,
+}
+
+impl Raid {
+    /// Get the Users who first spoke within a given time after the Raid.
+    ///
+    /// Input: `Duration`
+    /// Return: `Vec<&str>`
+    pub fn chatters(&self, within: Duration) -> Vec<&str> {
+        self.chatters.iter()
+            .filter(|(_, after)| *after <= within)
+            .map(|(user, _)| user.as_str())
+            .collect()
+    }
+}
+
+
+/// Raids: A mapping of Channel names to the most recent Raid on each.
+#[derive(Default)]
+pub struct Raids { inner: HashMap<String, Raid> }
+
+impl Raids {
+    /// Record a Raid on a Channel. Any previous Raid on it is forgotten.
+    ///
+    /// Input: `&str`, `&str`, `usize`, `Instant`
+    pub fn raid(&mut self, channel: &str, raider: &str, viewers: usize, now: Instant) {
+        self.inner.insert(channel.to_ascii_lowercase(), Raid {
+            raider: raider.to_owned(),
+            viewers,
+            time: now,
+            chatters: Vec::new(),
+        });
+    }
+
+    /// Record that a User has spoken in a Channel for the first time. If this
+    ///     is soon enough after a Raid, the User is counted as a Raider.
+    ///
+    /// Input: `&str`, `&str`, `Instant`
+    pub fn arrived(&mut self, channel: &str, user: &str, now: Instant) {
+        if let Some(raid) = self.inner.get_mut(&channel.to_ascii_lowercase()) {
+            let after = now.saturating_duration_since(raid.time);
+
+            if after <= TRACK_LIMIT {
+                raid.chatters.push((user.to_owned(), after));
+            }
+        }
+    }
+
+    /// Get the most recent Raid on a Channel.
+    ///
+    /// Input: `&str`
+    /// Return: `Option<&Raid>`
+    pub fn get(&self, channel: &str) -> Option<&Raid> {
+        self.inner.get(&channel.to_ascii_lowercase())
+    }
+}
+
+
+safe_static! {
+    pub static lazy RAIDS: RwLock<Raids> = Default::default();
+}
+
+
+#[cfg(test)]
+mod tests_raids {
+    use super::*;
+
+    /// Test to confirm that only Users arriving soon after a Raid are listed.
+    #[test]
+    fn test_raiders() {
+        let mut raids = Raids::default();
+        let t0 = Instant::now();
+        let minute = Duration::from_secs(60);
+
+        raids.arrived("#zxcv", "before", t0);
+        raids.raid("#zxcv", "asdfqwert", 42, t0);
+        raids.arrived("#zxcv", "first", t0 + minute);
+        raids.arrived("#zxcv", "second", t0 + minute * 4);
+        raids.arrived("#zxcv", "late", t0 + minute * 10);
+        raids.arrived("#zxcv", "never", t0 + TRACK_LIMIT + minute);
+        raids.arrived("#other", "elsewhere", t0 + minute);
+
+        let raid = raids.get("#ZXCV").expect("Raid not recorded.");
+
+        assert_eq!(vec!["first", "second"], raid.chatters(minute * 5));
+        assert_eq!(
+            vec!["first", "second", "late"],
+            raid.chatters(TRACK_LIMIT * 2),
+            "Users arriving after the tracking limit were recorded.",
+        );
+        assert!(raids.get("#other").is_none(), "Raid recorded in wrong Channel.");
+    }
+}
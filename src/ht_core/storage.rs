@@ -0,0 +1,112 @@
+//! Module for files kept by the Plugin, inside the HexChat config directory.
+
+use std::{
+    fs::{create_dir_all, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::PathBuf,
+};
+
+use hexchat::get_hexchat_config_dir;
+
+
+/// Name of the directory, inside the HexChat config directory, where all of
+///     the files of the Plugin are kept.
+const DIR_NAME: &str = "hextwitch";
+
+/// Mode of files which hold secrets, such as the Helix token: readable and
+///     writable only by their owner.
+#[cfg(unix)]
+const PRIVATE_MODE: u32 = 0o600;
+
+
+/// Find the full path of a file kept by the Plugin. Any directories leading up
+///     to the file are created if they do not exist.
+///
+/// Input: `&str`
+/// Return: `io::Result<PathBuf>`
+pub fn path(name: &str) -> io::Result<PathBuf> {
+    let mut path = PathBuf::from(get_hexchat_config_dir());
+    path.push(DIR_NAME);
+    path.push(name);
+
+    if let Some(dir) = path.parent() { create_dir_all(dir)?; }
+    Ok(path)
+}
+
+
+/// Add one line to the end of a file, creating the file if needed.
+///
+/// Input: `&str`, `&str`
+/// Return: `io::Result<()>`
+pub fn append_line(name: &str, line: &str) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path(name)?)?;
+    writeln!(file, "{}", line)
+}
+
+
+/// Replace the contents of a file with lines, creating the file if needed.
+///
+/// Input: `&str`, `&[String]`
+/// Return: `io::Result<()>`
+pub fn write_lines(name: &str, lines: &[String]) -> io::Result<()> {
+    let mut file = File::create(path(name)?)?;
+    for line in lines { writeln!(file, "{}", line)?; }
+    Ok(())
+}
+
+
+/// Restrict an existing private file so that, on Unix, it may only be read and
+///     written by its owner. A file which does not exist is left alone.
+///
+/// Input: `&str`
+/// Return: `io::Result<()>`
+pub fn restrict(name: &str) -> io::Result<()> {
+    #[cfg(unix)] {
+        use std::{fs::{Permissions, set_permissions}, os::unix::fs::PermissionsExt};
+
+        match set_permissions(path(name)?, Permissions::from_mode(PRIVATE_MODE)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+
+    #[cfg(not(unix))] { let _ = name; }
+    Ok(())
+}
+
+
+/// Replace the contents of a private file with lines, creating the file if
+///     needed. On Unix, a new file is created readable and writable only by
+///     its owner, and the mode of an existing file is tightened before
+///     anything is written to it.
+///
+/// Input: `&str`, `&[String]`
+/// Return: `io::Result<()>`
+pub fn write_private(name: &str, lines: &[String]) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.create(true).write(true);
+
+    #[cfg(unix)] {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(PRIVATE_MODE);
+    }
+
+    restrict(name)?;
+    let mut file = options.truncate(true).open(path(name)?)?;
+
+    for line in lines { writeln!(file, "{}", line)?; }
+    Ok(())
+}
+
+
+/// Read all lines of a file. A file which does not exist has no lines.
+///
+/// Input: `&str`
+/// Return: `io::Result<Vec<String>>`
+pub fn read_lines(name: &str) -> io::Result<Vec<String>> {
+    match File::open(path(name)?) {
+        Ok(file) => BufReader::new(file).lines().collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e),
+    }
+}
@@ -0,0 +1,311 @@
+//! Module for the live status of Channels. The API is polled periodically for
+//!     the Streams of all joined Channels, and the topic of each Tab is kept up
+//!     to date with whether the Channel is live, and what it is streaming.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use parking_lot::Mutex;
+
+use super::helix::{HelixBackend, HelixError};
+
+
+/// Default number of seconds between polls of the API.
+pub const POLL_DEFAULT: u64 = 120;
+/// Most Channels the API accepts in one request.
+const BATCH: usize = 100;
+
+
+/// Status: Whether a Channel is live, with what it is streaming, or was last
+///     set to stream.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Live { game: String, title: String, viewers: u64 },
+    Offline { game: String, title: String },
+}
+
+impl Status {
+    /// Return: `bool`
+    pub fn is_live(&self) -> bool { matches!(self, Self::Live { .. }) }
+
+    /// Build the topic of a Tab for this Status.
+    ///
+    /// Return: `String`
+    pub fn topic(&self) -> String {
+        let (state, game, title) = match self {
+            Self::Live { game, title, viewers } => (
+                format!(
+                    "\x0304[LIVE: {} viewer{}]\x0F",
+                    viewers,
+                    if *viewers == 1 { "" } else { "s" },
+                ),
+                game,
+                title,
+            ),
+            Self::Offline { game, title } => (String::from("[OFFLINE]"), game, title),
+        };
+
+        if game.is_empty() {
+            format!("{} {}", state, title)
+        } else {
+            format!("{} ({}) {}", state, game, title)
+        }
+    }
+}
+
+
+/// Update: What changed in a Channel when its Status was updated.
+#[derive(Debug, Default, PartialEq)]
+pub struct Update {
+    /// The new topic, if it is different.
+    pub topic: Option<String>,
+    /// `Some(true)` if the Channel went live, `Some(false)` if it went offline.
+    pub went_live: Option<bool>,
+}
+
+
+/// Tracker: The last known Status of each Channel, the time of the last poll,
+///     and whether a poll is still running.
+#[derive(Default)]
+pub struct Tracker {
+    inner: HashMap<String, Status>,
+    last: Option<Instant>,
+    polling: bool,
+    failing: bool,
+}
+
+impl Tracker {
+    /// Check whether it is time to poll again. If it is, the time of the last
+    ///     poll is set to now, and no other poll is due until it is finished.
+    ///
+    /// Input: `Duration`, `Instant`
+    /// Return: `bool`
+    pub fn due(&mut self, interval: Duration, now: Instant) -> bool {
+        match self.last {
+            _ if self.polling => false,
+            Some(last) if now.saturating_duration_since(last) < interval => false,
+            _ => {
+                self.last = Some(now);
+                self.polling = true;
+                true
+            }
+        }
+    }
+
+    /// Record that a poll has finished. Returns `true` if the poll failed, and
+    ///     the one before it did not, so that a failure is only reported once.
+    ///
+    /// Input: `bool`
+    /// Return: `bool`
+    pub fn finish(&mut self, ok: bool) -> bool {
+        let report = !ok && !self.failing;
+
+        self.polling = false;
+        self.failing = !ok;
+        report
+    }
+
+    /// Record the Status of a Channel. Going live or offline is only reported
+    ///     if the previous Status was known.
+    ///
+    /// Input: `&str`, `Status`
+    /// Return: `Update`
+    pub fn update(&mut self, channel: &str, status: Status) -> Update {
+        let topic = status.topic();
+        let live = status.is_live();
+
+        match self.inner.insert(channel.to_ascii_lowercase(), status) {
+            Some(old) => Update {
+                topic: Some(topic).filter(|t| *t != old.topic()),
+                went_live: Some(live).filter(|&l| l != old.is_live()),
+            },
+            None => Update { topic: Some(topic), went_live: None },
+        }
+    }
+
+    /// Forget a Channel, such as when its Tab is closed.
+    ///
+    /// Input: `&str`
+    pub fn forget(&mut self, channel: &str) {
+        self.inner.remove(&channel.to_ascii_lowercase());
+    }
+}
+
+
+safe_static! {
+    pub static lazy STREAMS: Mutex<Tracker> = Default::default();
+}
+
+
+/// Request the Status of Channels from the API. Each Channel is given as its
+///     name and the ID of its Broadcaster.
+///
+/// Input: `&mut dyn HelixBackend`, `&[(String, String)]`
+/// Return: `Result<Vec<(String, Status)>, HelixError>`
+pub fn poll(
+    api: &mut dyn HelixBackend,
+    channels: &[(String, String)],
+) -> Result<Vec<(String, Status)>, HelixError> {
+    let mut out: Vec<(String, Status)> = Vec::with_capacity(channels.len());
+
+    for batch in channels.chunks(BATCH) {
+        let logins: Vec<&str> = batch.iter()
+            .map(|(name, _)| name.trim_start_matches('#'))
+            .collect();
+        let live: HashMap<String, _> = api.streams(&logins)?.into_iter()
+            .map(|stream| (stream.user_login.to_ascii_lowercase(), stream))
+            .collect();
+
+        let offline: Vec<&str> = batch.iter()
+            .filter(|(name, _)| !live.contains_key(name.trim_start_matches('#')))
+            .map(|(_, id)| id.as_str())
+            .collect();
+        let info: HashMap<String, _> = if offline.is_empty() {
+            HashMap::new()
+        } else {
+            api.channels(&offline)?.into_iter()
+                .map(|channel| (channel.broadcaster_id.clone(), channel))
+                .collect()
+        };
+
+        for (name, id) in batch {
+            if let Some(stream) = live.get(name.trim_start_matches('#')) {
+                out.push((name.to_owned(), Status::Live {
+                    game: stream.game_name.clone(),
+                    title: stream.title.clone(),
+                    viewers: stream.viewer_count,
+                }));
+            } else if let Some(channel) = info.get(id) {
+                out.push((name.to_owned(), Status::Offline {
+                    game: channel.game_name.clone(),
+                    title: channel.title.clone(),
+                }));
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+
+#[cfg(test)]
+mod tests_streams {
+    use super::*;
+    use crate::ht_core::helix::{Channel, Stream, User};
+
+    /// A stand-in for the API, with one live Channel, and recording requests.
+    #[derive(Default)]
+    struct Fake { requests: Vec<String> }
+
+    impl HelixBackend for Fake {
+        fn users(&mut self, _logins: &[&str]) -> Result<Vec<User>, HelixError> {
+            Ok(Vec::new())
+        }
+
+        fn streams(&mut self, logins: &[&str]) -> Result<Vec<Stream>, HelixError> {
+            self.requests.push(format!("streams {}", logins.join(",")));
+            Ok(logins.iter().filter(|&&l| l == "live").map(|&l| Stream {
+                user_id: String::from("1"),
+                user_login: l.to_owned(),
+                user_name: l.to_owned(),
+                game_name: String::from("Chess"),
+                title: String::from("Playing"),
+                viewer_count: 5,
+                started_at: String::new(),
+            }).collect())
+        }
+
+        fn channels(&mut self, ids: &[&str]) -> Result<Vec<Channel>, HelixError> {
+            self.requests.push(format!("channels {}", ids.join(",")));
+            Ok(ids.iter().map(|&id| Channel {
+                broadcaster_id: id.to_owned(),
+                broadcaster_login: String::new(),
+                broadcaster_name: String::new(),
+                game_name: String::from("Chess"),
+                title: String::from("Playing"),
+            }).collect())
+        }
+
+        fn modify_channel(&mut self, _id: &str, _title: &str) -> Result<(), HelixError> {
+            Ok(())
+        }
+    }
+
+    fn live(viewers: u64) -> Status {
+        Status::Live {
+            game: String::from("Chess"),
+            title: String::from("Playing"),
+            viewers,
+        }
+    }
+
+    fn offline() -> Status {
+        Status::Offline { game: String::from("Chess"), title: String::from("Playing") }
+    }
+
+    /// Test to confirm that changes of Status are reported correctly.
+    #[test]
+    fn test_update() {
+        let mut tracker = Tracker::default();
+
+        let first = tracker.update("#zxcv", offline());
+        assert_eq!(None, first.went_live, "First Status reported as a change.");
+        assert_eq!(Some(String::from("[OFFLINE] (Chess) Playing")), first.topic);
+
+        assert_eq!(Update::default(), tracker.update("#zxcv", offline()),
+                   "Same Status reported as a change.");
+
+        let up = tracker.update("#ZXCV", live(1));
+        assert_eq!(Some(true), up.went_live, "Going live not reported.");
+        assert_eq!(Some(String::from("\x0304[LIVE: 1 viewer]\x0F (Chess) Playing")), up.topic);
+
+        let more = tracker.update("#zxcv", live(5));
+        assert_eq!(None, more.went_live, "Viewers reported as going live.");
+        assert!(more.topic.is_some(), "Viewers not updated in topic.");
+
+        assert_eq!(Some(false), tracker.update("#zxcv", offline()).went_live,
+                   "Going offline not reported.");
+    }
+
+    /// Test to confirm that live Channels are found by their Streams, and that
+    ///     only the others are looked up as Channels.
+    #[test]
+    fn test_poll() {
+        let mut api = Fake::default();
+        let channels = vec![
+            (String::from("#live"), String::from("1")),
+            (String::from("#asleep"), String::from("2")),
+        ];
+
+        let result = poll(&mut api, &channels).expect("Poll failed.");
+
+        assert_eq!(vec!["streams live,asleep", "channels 2"], api.requests,
+                   "Wrong requests.");
+        assert_eq!(vec![
+            (String::from("#live"), live(5)),
+            (String::from("#asleep"), offline()),
+        ], result, "Wrong Statuses.");
+    }
+
+    /// Test to confirm that polls are spaced by the interval, never overlap,
+    ///     and report failures once.
+    #[test]
+    fn test_due() {
+        let mut tracker = Tracker::default();
+        let t0 = Instant::now();
+        let interval = Duration::from_secs(POLL_DEFAULT);
+
+        assert!(tracker.due(interval, t0), "First poll not due.");
+        assert!(!tracker.due(interval, t0 + interval), "Poll due while running.");
+        assert!(tracker.finish(false), "Failure not reported.");
+        assert!(!tracker.due(interval, t0 + interval / 2), "Poll due too early.");
+        assert!(tracker.due(interval, t0 + interval), "Poll not due after interval.");
+        assert!(!tracker.finish(false), "Failure reported twice.");
+        assert!(tracker.due(interval, t0 + interval * 2));
+        assert!(!tracker.finish(true), "Success reported as a failure.");
+        assert!(tracker.due(interval, t0 + interval * 3));
+        assert!(tracker.finish(false), "New failure not reported.");
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use parking_lot::RwLock;
+
+
+/// Default number of minutes a User may go without speaking before they are
+///     removed from the User List of a Channel.
+pub const IDLE_DEFAULT: u64 = 30;
+
+
+/// Channel: The Users known in one Channel. A User is in the User List of the
+///     Channel once they are in `seen` but NOT in `pending`.
+#[derive(Default)]
+struct Channel {
+    seen: HashMap<String, Instant>,
+    pending: HashSet<String>,
+}
+
+
+/// Users: A mapping of Channel names to the Users present in them, with the
+///     time at which each User was last seen. This mirrors the User Lists that
+///     the Plugin builds in HexChat with fake `JOIN`s, so that idle Users can
+///     be removed again with fake `PART`s, and so that new Users can be Joined
+///     in batches rather than on every Message.
+#[derive(Default)]
+pub struct Users { inner: HashMap<String, Channel> }
+
+impl Users {
+    /// Record that a User has been seen in a Channel. If the User was NOT
+    ///     already present, they are queued to be Joined by the next call to
+    ///     `flush()`, and `true` is returned.
+    ///
+    /// Input: `&str`, `&str`, `Instant`
+    /// Return: `bool`
+    pub fn seen(&mut self, channel: &str, user: &str, now: Instant) -> bool {
+        let chan = self.inner.entry(channel.to_ascii_lowercase()).or_default();
+        let user = user.to_ascii_lowercase();
+
+        if chan.seen.insert(user.clone(), now).is_none() {
+            chan.pending.insert(user);
+            true
+        } else { false }
+    }
+
+    /// Record that a User has really Joined a Channel. They are now in the User
+    ///     List, and do not need to be Joined by the Plugin.
+    ///
+    /// Input: `&str`, `&str`, `Instant`
+    pub fn joined(&mut self, channel: &str, user: &str, now: Instant) {
+        let chan = self.inner.entry(channel.to_ascii_lowercase()).or_default();
+        let user = user.to_ascii_lowercase();
+
+        chan.pending.remove(&user);
+        chan.seen.insert(user, now);
+    }
+
+    /// Remove a User from a Channel. Returns `true` if the User was present.
+    ///
+    /// Input: `&str`, `&str`
+    /// Return: `bool`
+    pub fn part(&mut self, channel: &str, user: &str) -> bool {
+        match self.inner.get_mut(&channel.to_ascii_lowercase()) {
+            Some(chan) => {
+                let user = user.to_ascii_lowercase();
+                chan.pending.remove(&user);
+                chan.seen.remove(&user).is_some()
+            }
+            None => false,
+        }
+    }
+
+    /// Forget all Users of a Channel, because the User List of its Tab has been
+    ///     emptied by HexChat.
+    ///
+    /// Input: `&str`
+    pub fn clear(&mut self, channel: &str) {
+        self.inner.remove(&channel.to_ascii_lowercase());
+    }
+
+    /// Forget all Users of all Channels.
+    pub fn clear_all(&mut self) { self.inner.clear(); }
+
+    /// Take all Users waiting to be Joined, grouped by Channel. They are then
+    ///     considered to be in the User List.
+    ///
+    /// Return: `Vec<(String, Vec<String>)>`
+    pub fn flush(&mut self) -> Vec<(String, Vec<String>)> {
+        self.inner.iter_mut()
+            .filter(|(_, chan)| !chan.pending.is_empty())
+            .map(|(channel, chan)| (
+                channel.clone(),
+                chan.pending.drain().collect(),
+            ))
+            .collect()
+    }
+
+    /// Return Users taken by `flush()` to the queue, because they could not be
+    ///     Joined. Users who have since left the Channel are not returned.
+    ///
+    /// Input: `&str`, `Vec<String>`
+    pub fn restore(&mut self, channel: &str, users: Vec<String>) {
+        if let Some(chan) = self.inner.get_mut(&channel.to_ascii_lowercase()) {
+            for user in users {
+                if chan.seen.contains_key(&user) { chan.pending.insert(user); }
+            }
+        }
+    }
+
+    /// Remove all Users who have not been seen within the given timeout, and
+    ///     return them as pairs of Channel and User names. Users who were never
+    ///     Joined are removed, but not returned.
+    ///
+    /// Input: `Duration`, `Instant`
+    /// Return: `Vec<(String, String)>`
+    pub fn expire(&mut self, timeout: Duration, now: Instant) -> Vec<(String, String)> {
+        let mut idle: Vec<(String, String)> = Vec::new();
+
+        for (channel, chan) in self.inner.iter_mut() {
+            let pending = &mut chan.pending;
+
+            chan.seen.retain(|user, last| {
+                if now.saturating_duration_since(*last) < timeout {
+                    true
+                } else {
+                    if !pending.remove(user) {
+                        idle.push((channel.clone(), user.clone()));
+                    }
+                    false
+                }
+            });
+        }
+
+        self.inner.retain(|_, chan| !chan.seen.is_empty());
+        idle
+    }
+}
+
+
+safe_static! {
+    pub static lazy USERS: RwLock<Users> = Default::default();
+}
+
+
+#[cfg(test)]
+mod tests_users {
+    use super::*;
+
+    /// Test to confirm that Users are removed once they have been idle for
+    ///     exactly the timeout, and that only Joined Users are returned.
+    #[test]
+    fn test_expire() {
+        let mut users = Users::default();
+        let t0 = Instant::now();
+        let timeout = Duration::from_secs(60);
+
+        assert!(users.seen("#Asdf", "Qwert", t0), "New User not reported.");
+        assert!(!users.seen("#asdf", "qwert", t0), "Known User reported as new.");
+        users.flush();
+        users.seen("#asdf", "zxcv", t0);
+
+        assert!(
+            users.expire(timeout, t0 + timeout - Duration::from_secs(1)).is_empty(),
+            "User removed before the timeout.",
+        );
+        assert_eq!(
+            vec![(String::from("#asdf"), String::from("qwert"))],
+            users.expire(timeout, t0 + timeout),
+            "Idle Users not removed at the timeout, or unjoined User returned.",
+        );
+        assert!(users.flush().is_empty(), "Expired User still waiting to be Joined.");
+        assert!(users.seen("#asdf", "qwert", t0 + timeout), "Expired User still known.");
+    }
+
+    /// Test to confirm that Users are forgotten when they Part, and when their
+    ///     Channel is cleared.
+    #[test]
+    fn test_part_clear() {
+        let mut users = Users::default();
+        let t0 = Instant::now();
+
+        users.seen("#asdf", "qwert", t0);
+        assert!(users.part("#ASDF", "QWERT"), "Part of known User returns false.");
+        assert!(!users.part("#asdf", "qwert"), "Part of unknown User returns true.");
+        assert!(!users.part("#zxcv", "qwert"), "Part in unknown Channel returns true.");
+        assert!(users.flush().is_empty(), "Parted User still waiting to be Joined.");
+
+        users.seen("#asdf", "qwert", t0);
+        users.seen("#zxcv", "qwert", t0);
+        users.clear("#asdf");
+        assert_eq!(
+            vec![(String::from("#zxcv"), vec![String::from("qwert")])],
+            users.flush(),
+            "Clearing a Channel affects another.",
+        );
+
+        users.clear_all();
+        assert!(users.seen("#zxcv", "qwert", t0), "User known after clearing all.");
+    }
+    /// Test to confirm that Users who could not be Joined are queued again,
+    ///     unless they have left the Channel in the meantime.
+    #[test]
+    fn test_restore() {
+        let mut users = Users::default();
+        let t0 = Instant::now();
+
+        users.seen("#asdf", "qwert", t0);
+        users.seen("#asdf", "zxcv", t0);
+
+        let mut flushed = users.flush();
+        assert!(users.flush().is_empty(), "Flushed Users still waiting.");
+
+        let (channel, mut names) = flushed.pop().expect("No Users flushed.");
+        names.sort();
+        assert_eq!("#asdf", channel, "Users flushed for the wrong Channel.");
+        assert_eq!(vec!["qwert", "zxcv"], names, "Wrong Users flushed.");
+
+        users.part("#asdf", "zxcv");
+        users.restore("#ASDF", names.clone());
+        assert_eq!(
+            vec![(String::from("#asdf"), vec![String::from("qwert")])],
+            users.flush(),
+            "Restored Users not queued again, or Parted User restored.",
+        );
+
+        users.clear("#asdf");
+        users.restore("#asdf", names);
+        assert!(users.flush().is_empty(), "Users restored into a cleared Channel.");
+    }
+}
@@ -0,0 +1,691 @@
+//! Module for the sending, storage and replay of Twitch Whisper conversations.
+//!     Each conversation partner has a log file, with one Whisper on each line.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    io,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use hexchat::{
+    ChannelRef,
+    get_channel,
+    get_channel_name,
+    get_pref_int,
+    get_pref_string,
+    print_event_to_channel,
+    PrintEvent,
+};
+use parking_lot::{Mutex, RwLock};
+
+use super::{
+    irc::{escape, Message, split_at_char, unescape},
+    outgoing,
+    output::{Badges, USERSTATE},
+    storage,
+};
+
+
+/// Default number of Whispers replayed into a newly opened Whisper Tab.
+pub const REPLAY_DEFAULT: usize = 10;
+/// Length of time after which a sent Whisper is assumed to have been delivered,
+///     if Twitch has not reported a failure.
+const PENDING_TIMEOUT: Duration = Duration::from_secs(10);
+
+
+/// Entry: A single Whisper in a conversation log. Actions are stored with the
+///     same `/me ` prefix that Twitch uses for them.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub time: DateTime<Utc>,
+    pub author: String,
+    pub text: String,
+}
+
+impl Entry {
+    /// Input: `&str`, `&str`
+    /// Return: `Entry`
+    pub fn new(author: &str, text: &str) -> Self {
+        Self {
+            time: Utc::now(),
+            author: author.to_owned(),
+            text: text.to_owned(),
+        }
+    }
+
+    /// Print this Entry into a Whisper Tab, in dim text, marked with the time
+    ///     it was originally sent.
+    ///
+    /// Input: `&ChannelRef`
+    pub fn replay(&self, tab: &ChannelRef) {
+        let (etype, text) = match self.text.strip_prefix("/me ") {
+            Some(action) => (PrintEvent::PRIVATE_ACTION_TO_DIALOG, action),
+            None => (PrintEvent::PRIVATE_MESSAGE_TO_DIALOG, self.text.as_str()),
+        };
+
+        print_event_to_channel(tab, etype, &[
+            self.author.as_str(),
+            &format!(
+                "\x0314[{}] {}\x0F",
+                self.time.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                text,
+            ),
+            "",
+        ]);
+    }
+}
+
+impl fmt::Display for Entry {
+    /// Format this Entry into a single line for a log file.
+    ///
+    /// Return: `fmt::Result`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f, "{} {} {}",
+            self.time.timestamp_millis(),
+            self.author,
+            escape(&self.text),
+        )
+    }
+}
+
+impl std::str::FromStr for Entry {
+    type Err = ();
+
+    /// Read a single line of a log file into an Entry.
+    ///
+    /// Input: `&str`
+    /// Return: `Result<Entry, ()>`
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let (time, rest) = split_at_char(line, ' ');
+        let (author, text) = split_at_char(rest, ' ');
+
+        if author.is_empty() { return Err(()); }
+
+        Ok(Self {
+            time: Utc.timestamp_millis_opt(time.parse().map_err(|_| ())?)
+                .single()
+                .ok_or(())?,
+            author: author.to_owned(),
+            text: unescape(text),
+        })
+    }
+}
+
+
+/// Partner: What is known about a conversation partner, from the Tags of the
+///     Whispers they have sent during this session.
+pub struct Partner {
+    pub display: String,
+    pub badges: Badges,
+    /// The chat color of the Partner, as a hex code such as `#DABEEF`.
+    pub color: Option<String>,
+    pub thread: Option<String>,
+    pub user_id: Option<String>,
+    pub first: DateTime<Local>,
+}
+
+impl Partner {
+    /// Summarize the Partner, for use as the Topic of their Whisper Tab.
+    ///
+    /// Return: `String`
+    pub fn topic(&self) -> String {
+        format!(
+            "{}{}{} - Whispering since {}",
+            self.badges.output,
+            self.display,
+            self.color.as_ref().map(|c| format!(" [{}]", c)).unwrap_or_default(),
+            self.first.format("%Y-%m-%d %H:%M"),
+        )
+    }
+
+    /// Get the name of the Partner, in the IRC color nearest to their chat
+    ///     color, for printing as the author of their Whispers.
+    ///
+    /// Return: `String`
+    pub fn nick(&self) -> String {
+        match self.color.as_deref().and_then(irc_color) {
+            Some(code) => format!("\x03{:02}{}\x0F", code, self.display),
+            None => self.display.clone(),
+        }
+    }
+}
+
+
+/// Find the IRC color nearest to a hex color code, such as `#DABEEF`. Black and
+///     white are left out, so that the name stays readable on any background.
+///
+/// Input: `&str`
+/// Return: `Option<u8>`
+fn irc_color(hex: &str) -> Option<u8> {
+    const PALETTE: &[(u8, (i32, i32, i32))] = &[
+        (2, (0, 0, 127)),
+        (3, (0, 147, 0)),
+        (4, (255, 0, 0)),
+        (5, (127, 0, 0)),
+        (6, (156, 0, 156)),
+        (7, (252, 127, 0)),
+        (8, (255, 255, 0)),
+        (9, (0, 252, 0)),
+        (10, (0, 147, 147)),
+        (11, (0, 255, 255)),
+        (12, (0, 0, 252)),
+        (13, (255, 0, 255)),
+        (14, (127, 127, 127)),
+        (15, (210, 210, 210)),
+    ];
+
+    let hex = hex.strip_prefix('#')?;
+    if hex.len() != 6 { return None; }
+
+    let channel = |i: usize| i32::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    let (r, g, b) = (channel(0)?, channel(2)?, channel(4)?);
+
+    PALETTE.iter()
+        .min_by_key(|(_, (pr, pg, pb))| (r - pr).pow(2) + (g - pg).pow(2) + (b - pb).pow(2))
+        .map(|&(code, _)| code)
+}
+
+
+/// Get the name to show for a User. This is their Display Name, unless it is
+///     not simply a different capitalization of their login, in which case the
+///     login is added to it.
+///
+/// Input: `&str`, `&str`
+/// Return: `String`
+fn display_name(login: &str, display: &str) -> String {
+    if display.is_empty() {
+        login.to_owned()
+    } else if display.eq_ignore_ascii_case(login) {
+        display.to_owned()
+    } else {
+        format!("{} ({})", display, login)
+    }
+}
+
+
+/// Partners: A mapping of usernames to the Partners they represent.
+#[derive(Default)]
+pub struct Partners { inner: HashMap<String, Partner> }
+
+impl Partners {
+    /// Get a Partner by username.
+    ///
+    /// Input: `&str`
+    /// Return: `Option<&Partner>`
+    pub fn get(&self, user: &str) -> Option<&Partner> {
+        self.inner.get(&user.to_ascii_lowercase())
+    }
+
+    /// Update the Partner who sent a Whisper, from its Tags. Returns `true` if
+    ///     the Topic of the Partner has changed.
+    ///
+    /// Input: `&Message`
+    /// Return: `bool`
+    pub fn update(&mut self, msg: &Message) -> bool {
+        let login = msg.author().to_ascii_lowercase();
+        let display = display_name(
+            &login,
+            &msg.get_tag("display-name").unwrap_or_default(),
+        );
+        let badges = msg.get_tag("badges").unwrap_or_default();
+        let info = msg.get_tag("badge-info").unwrap_or_default();
+        let color = msg.get_tag("color").filter(|c| !c.is_empty());
+
+        match self.inner.get_mut(&login) {
+            Some(partner) => {
+                let topic = partner.topic();
+
+                partner.display = display;
+                partner.badges = Badges::from_str(&badges, &info);
+                partner.color = color;
+                if let Some(thread) = msg.get_tag("thread-id") {
+                    partner.thread.replace(thread);
+                }
+                if let Some(id) = msg.get_tag("user-id") {
+                    partner.user_id.replace(id);
+                }
+
+                partner.topic() != topic
+            }
+            None => {
+                self.inner.insert(login, Partner {
+                    display,
+                    badges: Badges::from_str(&badges, &info),
+                    color,
+                    thread: msg.get_tag("thread-id"),
+                    user_id: msg.get_tag("user-id"),
+                    first: Local::now(),
+                });
+                true
+            }
+        }
+    }
+}
+
+
+safe_static! {
+    pub static lazy PARTNERS: RwLock<Partners> = Default::default();
+}
+
+
+/// Route: A rule for where a received Whisper should be shown, besides its own
+///     Tab. Rules can be set as a default, and for specific senders.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Route {
+    /// Only show the Whisper in its own Tab.
+    Tab,
+    /// Also show the Whisper in the current Tab.
+    Always,
+    /// Also show the Whisper in the focused Tab, if it is not on Twitch.
+    Unfocused,
+    /// Also show the Whisper in a shared `(whispers)` Tab.
+    Aggregate,
+    /// Do not show the Whisper anywhere, and do not open a Tab for it. It is
+    ///     still logged.
+    Ignore,
+}
+
+impl Route {
+    pub const ALL: &'static [Self] = &[
+        Self::Tab,
+        Self::Always,
+        Self::Unfocused,
+        Self::Aggregate,
+        Self::Ignore,
+    ];
+
+    /// Find the Route with a given name.
+    ///
+    /// Input: `&str`
+    /// Return: `Option<Route>`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|r| name.eq_ignore_ascii_case(r.name()))
+    }
+
+    /// Return: `&str`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tab => "tab",
+            Self::Always => "always",
+            Self::Unfocused => "unfocused",
+            Self::Aggregate => "aggregate",
+            Self::Ignore => "ignore",
+        }
+    }
+
+    /// Get the name of the Preference storing the Route for a sender, or the
+    ///     default Route if no sender is given.
+    ///
+    /// Input: `Option<&str>`
+    /// Return: `String`
+    pub fn pref(user: Option<&str>) -> String {
+        match user {
+            Some(user) => format!("PREF_whisper_route_{}", user.to_ascii_lowercase()),
+            None => String::from("PREF_whisper_route"),
+        }
+    }
+
+    /// Get the default Route. If none has been set, the older toggle for also
+    ///     showing Whispers in the current Tab is respected.
+    ///
+    /// Return: `Route`
+    pub fn default_route() -> Self {
+        match get_pref_string(&Self::pref(None)).and_then(|r| Self::from_name(&r)) {
+            Some(route) => route,
+            None if get_pref_int("PREF_whispers_in_current").unwrap_or(0) != 0 => {
+                Self::Always
+            }
+            None => Self::Tab,
+        }
+    }
+
+    /// Get the Route for Whispers from a sender.
+    ///
+    /// Input: `&str`
+    /// Return: `Route`
+    pub fn for_user(user: &str) -> Self {
+        get_pref_string(&Self::pref(Some(user)))
+            .and_then(|r| Self::from_name(&r))
+            .unwrap_or_else(Self::default_route)
+    }
+}
+
+
+/// Name of the Tab which collects Whispers from senders with the `Aggregate`
+///     Route.
+pub const AGGREGATE_TAB: &str = "(whispers)";
+
+
+/// Pending: A Whisper which has been sent, but which Twitch may still report as
+///     having failed.
+struct Pending {
+    target: String,
+    text: String,
+    time: Instant,
+    echoed: bool,
+}
+
+
+/// Outbox: A queue of recently sent Whispers, each tracked once, in the order
+///     they were sent. Whispers sent by the Plugin are added as they are sent,
+///     and Whispers typed directly by the User are added by their echo. When
+///     Twitch reports that a Whisper failed, the failure is matched to the
+///     oldest Whisper in the queue.
+#[derive(Default)]
+pub struct Outbox { pending: VecDeque<Pending> }
+
+impl Outbox {
+    /// Drop Whispers which have been in the queue long enough that they must
+    ///     have been delivered.
+    ///
+    /// Input: `Instant`
+    fn expire(&mut self, now: Instant) {
+        while let Some(first) = self.pending.front() {
+            if now.saturating_duration_since(first.time) < PENDING_TIMEOUT {
+                break;
+            }
+            self.pending.pop_front();
+        }
+    }
+
+    /// Add a Whisper to the queue as it is sent.
+    ///
+    /// Input: `&str`, `&str`, `Instant`
+    pub fn push(&mut self, target: &str, text: &str, now: Instant) {
+        self.expire(now);
+        self.pending.push_back(Pending {
+            target: target.to_ascii_lowercase(),
+            text: text.to_owned(),
+            time: now,
+            echoed: false,
+        });
+    }
+
+    /// Stop tracking a Whisper which could not be sent after all. The newest
+    ///     matching Whisper which has not been echoed is removed.
+    ///
+    /// Input: `&str`, `&str`
+    pub fn cancel(&mut self, target: &str, text: &str) {
+        let target = target.to_ascii_lowercase();
+
+        if let Some(idx) = self.pending.iter().rposition(|p| {
+            !p.echoed && p.target == target && p.text == text
+        }) {
+            self.pending.remove(idx);
+        }
+    }
+
+    /// Record the echo of a Whisper. A Whisper sent by the Plugin is already
+    ///     in the queue, and is only marked as echoed. Any other Whisper was
+    ///     sent directly by the User, and is added to the queue now, so that a
+    ///     failure can still be matched to it.
+    ///
+    /// Input: `&str`, `&str`, `Instant`
+    pub fn echo(&mut self, target: &str, text: &str, now: Instant) {
+        self.expire(now);
+        let target = target.to_ascii_lowercase();
+
+        match self.pending.iter_mut().find(|p| {
+            !p.echoed && p.target == target && p.text == text
+        }) {
+            Some(pending) => pending.echoed = true,
+            None => self.pending.push_back(Pending {
+                target,
+                text: text.to_owned(),
+                time: now,
+                echoed: true,
+            }),
+        }
+    }
+
+    /// Take the oldest Whisper out of the queue, because Twitch has reported
+    ///     that it failed. Returns the target and the text of the Whisper.
+    ///
+    /// Input: `Instant`
+    /// Return: `Option<(String, String)>`
+    pub fn fail(&mut self, now: Instant) -> Option<(String, String)> {
+        self.expire(now);
+        self.pending.pop_front().map(|p| (p.target, p.text))
+    }
+}
+
+
+safe_static! {
+    pub static lazy OUTBOX: Mutex<Outbox> = Default::default();
+}
+
+
+/// Split a Whisper Command, in either the `.w` or `/w` form, into the target
+///     and the text of the Whisper.
+///
+/// Input: `&str`
+/// Return: `Option<(&str, &str)>`
+pub fn parse_command(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix(".w ").or_else(|| line.strip_prefix("/w "))?;
+    let (target, text) = split_at_char(rest.trim_start(), ' ');
+
+    if target.is_empty() || text.trim().is_empty() {
+        None
+    } else {
+        Some((target, text.trim()))
+    }
+}
+
+
+/// Send a Whisper to a User. Twitch only accepts Whispers sent into a Channel,
+///     so if the current Tab is not a Channel, any Channel in which the User
+///     State is known is used instead. The Whisper is sent as soon as the rate
+///     limits allow, and enters the Outbox only then.
+///
+/// Input: `&str`, `&str`
+/// Return: `bool`
+pub fn send(target: &str, text: &str) -> bool {
+    let command = format!("SAY .w {} {}", target, text);
+    let current = get_channel_name();
+
+    let from: Option<String> = if current.starts_with('#') {
+        Some(current)
+    } else {
+        USERSTATE.read().channels()
+            .find(|ch| get_channel("Twitch", ch).is_some())
+            .map(String::from)
+    };
+
+    match from {
+        Some(channel) => {
+            outgoing::send(&channel, command);
+            true
+        }
+        //  Nowhere to send it from.
+        None => false,
+    }
+}
+
+
+/// Get the name of the log file for a conversation partner. Twitch usernames
+///     are limited to ASCII letters, numbers and underscores, so any other name
+///     is rejected, to keep the path safe and distinct for each User.
+///
+/// Input: `&str`
+/// Return: `io::Result<String>`
+fn log_name(partner: &str) -> io::Result<String> {
+    if partner.is_empty()
+        || !partner.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Not a valid username: {:?}", partner),
+        ));
+    }
+
+    Ok(format!("whispers/{}.log", partner.to_ascii_lowercase()))
+}
+
+
+/// Add a Whisper to the log of a conversation.
+///
+/// Input: `&str`, `&Entry`
+/// Return: `io::Result<()>`
+pub fn record(partner: &str, entry: &Entry) -> io::Result<()> {
+    storage::append_line(&log_name(partner)?, &entry.to_string())
+}
+
+
+/// Read Whispers in the log of a conversation, oldest first. These are the most
+///     recent, after skipping back over a number of even more recent ones.
+///
+/// Input: `&str`, `usize`, `usize`
+/// Return: `io::Result<Vec<Entry>>`
+pub fn history(partner: &str, count: usize, skip: usize) -> io::Result<Vec<Entry>> {
+    let lines = storage::read_lines(&log_name(partner)?)?;
+
+    Ok(page(&lines, count, skip).iter()
+        .filter_map(|line| line.parse().ok())
+        .collect())
+}
+
+
+/// Select a page of lines, counting back from the end.
+///
+/// Input: `&[String]`, `usize`, `usize`
+/// Return: `&[String]`
+fn page(lines: &[String], count: usize, skip: usize) -> &[String] {
+    let end = lines.len().saturating_sub(skip);
+    &lines[end.saturating_sub(count)..end]
+}
+
+
+/// Replay the most recent Whispers of a conversation into its Tab. The number
+///     of Whispers is set by `PREF_whisper_replay`.
+///
+/// Input: `&str`, `&ChannelRef`
+pub fn replay(partner: &str, tab: &ChannelRef) {
+    let count = get_pref_int("PREF_whisper_replay")
+        .map_or(REPLAY_DEFAULT, |n| n.max(0) as usize);
+
+    if count > 0 {
+        if let Ok(entries) = history(partner, count, 0) {
+            for entry in entries { entry.replay(tab); }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests_whispers {
+    use super::*;
+
+    /// Test to confirm that Partners are named and updated from Whisper Tags.
+    #[test]
+    fn test_partners() {
+        let mut partners = Partners::default();
+        let msg: Message = r"@badges=premium/1;color=#DABEEF;display-name=Asdf\sQwert;emotes=;message-id=2;thread-id=1337_9001;turbo=0;user-id=123456789;user-type= :asdfqwert!asdfqwert@asdfqwert.tmi.twitch.tv WHISPER thyself :asdf"
+            .parse().expect("Failed to parse Whisper.");
+
+        assert!(partners.update(&msg), "New Partner does not change Topic.");
+        assert!(!partners.update(&msg), "Same Whisper changes Topic.");
+
+        let partner = partners.get("AsdfQwert").expect("Partner is not stored.");
+        assert_eq!("Asdf Qwert (asdfqwert)", partner.display);
+        assert_eq!("± ", partner.badges.output);
+        assert_eq!(Some("1337_9001"), partner.thread.as_deref());
+        assert_eq!(Some("123456789"), partner.user_id.as_deref());
+        assert_eq!(Some("#DABEEF"), partner.color.as_deref());
+        assert!(partner.topic().contains("[#DABEEF]"), "Color not in Topic.");
+        assert_eq!("\x0315Asdf Qwert (asdfqwert)\x0F", partner.nick(), "Wrong nick color.");
+
+        let recolored: Message = r"@badges=premium/1;color=#FF0000;display-name=Asdf\sQwert;user-id=123456789 :asdfqwert!asdfqwert@asdfqwert.tmi.twitch.tv WHISPER thyself :asdf"
+            .parse().expect("Failed to parse Whisper.");
+        assert!(partners.update(&recolored), "New color does not change Topic.");
+        assert_eq!("\x0304Asdf Qwert (asdfqwert)\x0F", partners.get("asdfqwert").unwrap().nick());
+    }
+
+    /// Test to confirm that log files are named only for valid usernames.
+    #[test]
+    fn test_log_name() {
+        assert_eq!("whispers/asdf_qwert.log", log_name("Asdf_Qwert").unwrap());
+        assert!(log_name("").is_err(), "Empty name accepted.");
+        assert!(log_name("../asdf").is_err(), "Path accepted.");
+        assert!(log_name("a.b").is_err(), "Invalid name accepted.");
+    }
+
+    /// Test to confirm that pages of history count back from the most recent.
+    #[test]
+    fn test_page() {
+        let lines: Vec<String> = (1..=5).map(|n| n.to_string()).collect();
+
+        assert_eq!(&["4", "5"], page(&lines, 2, 0), "Wrong most recent page.");
+        assert_eq!(&["2", "3"], page(&lines, 2, 2), "Wrong older page.");
+        assert_eq!(&["1"], page(&lines, 3, 4), "Wrong oldest page.");
+        assert!(page(&lines, 3, 9).is_empty(), "Page beyond the log is not empty.");
+    }
+
+    /// Test to confirm that both forms of Whisper Command are understood.
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(Some(("asdf", "qwert zxcv")), parse_command(".w asdf qwert zxcv"));
+        assert_eq!(Some(("asdf", "/me qwert")), parse_command("/w asdf /me qwert"));
+        assert_eq!(None, parse_command(".w asdf"), "Empty Whisper is parsed.");
+        assert_eq!(None, parse_command("asdf qwert"), "Plain text is parsed.");
+    }
+
+    /// Test to confirm that echoes are matched to sent Whispers only once, and
+    ///     that failures are matched to the oldest Whisper.
+    #[test]
+    fn test_outbox() {
+        let mut outbox = Outbox::default();
+        let t0 = Instant::now();
+
+        outbox.push("Asdf", "hello", t0);
+        outbox.echo("asdf", "hello", t0);
+        //  The same text typed again directly by the User.
+        outbox.echo("asdf", "hello", t0);
+        outbox.push("qwert", "second", t0);
+
+        for expected in [("asdf", "hello"), ("asdf", "hello"), ("qwert", "second")] {
+            assert_eq!(
+                Some((String::from(expected.0), String::from(expected.1))),
+                outbox.fail(t0),
+                "Failures are not matched to each Whisper once, in order.",
+            );
+        }
+        assert_eq!(None, outbox.fail(t0), "Echo of a sent Whisper is tracked twice.");
+
+        outbox.push("qwert", "hi", t0);
+        outbox.cancel("QWERT", "hi");
+        assert_eq!(None, outbox.fail(t0), "Canceled Whisper is still tracked.");
+
+        outbox.push("qwert", "hi", t0);
+        assert_eq!(
+            None,
+            outbox.fail(t0 + PENDING_TIMEOUT),
+            "Failure is matched to an expired Whisper.",
+        );
+    }
+
+    /// Test to confirm that an Entry survives being written to and read from a
+    ///     line of a log file.
+    #[test]
+    fn test_entry_consistency() {
+        for text in &["asdf qwert", "/me does a thing; twice", "a\\b\nc"] {
+            let entry = Entry::new("asdfqwert", text);
+            let line = entry.to_string();
+
+            assert!(!line.contains('\n'), "Entry is written on multiple lines.");
+
+            let mut back: Entry = line.parse().expect("Failed to parse Entry.");
+            back.time = entry.time;
+
+            assert_eq!(entry, back, "Entry is not the same after parsing.");
+        }
+
+        assert_eq!(
+            Err(()),
+            "asdf".parse::<Entry>(),
+            "Line without an author is parsed.",
+        );
+    }
+}
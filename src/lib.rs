@@ -10,9 +10,12 @@ extern crate hexchat;
 mod ht_core;
 
 
+use std::time::Duration;
+
 use hexchat::{
     add_print_event_listener,
     add_raw_server_event_listener,
+    add_timer_task,
     add_window_event_listener,
     Command,
     deregister_command,
@@ -25,22 +28,57 @@ use hexchat::{
     register_command,
     remove_print_event_listener,
     remove_raw_server_event_listener,
+    remove_timer_task,
     remove_window_event_listener,
+    TimerTask,
     WindowEvent,
     WindowEventListener,
 };
 
 use ht_core::{
+    cb_close,
     cb_focus,
+    cb_gifts,
+    cb_helix,
+    cb_idle,
+    cb_input,
     cb_join,
+    cb_joins,
+    cb_outgoing,
+    cb_part,
     cb_print,
+    cb_self,
     cb_server,
+    cb_streams,
+    cb_topic,
+    cmd_big_cheer,
+    cmd_bits,
+    cmd_bits_keep,
+    cmd_chatters,
+    cmd_cheermotes,
+    cmd_dupe_bypass,
+    cmd_first_alert,
+    cmd_gifts,
+    cmd_helix,
+    cmd_hilite,
     cmd_ht_debug,
+    cmd_idle,
+    cmd_lag_mark,
+    cmd_notices,
+    cmd_raid_greeting,
+    cmd_raiders,
     cmd_reward,
+    cmd_stream_poll,
+    cmd_tabcolor,
+    cmd_tgroup,
+    cmd_tignore,
     cmd_title,
     cmd_tjoin,
+    cmd_twhois,
+    cmd_unread,
     cmd_whisper,
     cmd_whisper_here,
+    cmd_whistory,
 };
 
 
@@ -48,6 +86,7 @@ enum Hook {
     CommandHook(Command),
     PrintHook(PrintEventListener),
     ServerHook(RawServerEventListener),
+    TimerHook(TimerTask),
     WindowHook(WindowEventListener),
 }
 
@@ -75,12 +114,152 @@ impl Plugin for HexTwitch {
         let mut hooks: Vec<Hook> = Vec::with_capacity(15);
 
         //  Register Plugin Commands, with helptext.
+        hooks.push(Hook::CommandHook(register_command(
+            "BIGCHEER",
+            "Set the number of Bits from which a Cheer is alerted as a big \
+                Cheer, with the \"bigcheer\" Tab Color. Zero disables the \
+                alert.\n\n\
+                Usage: BIGCHEER [<bits>]",
+            Priority::NORMAL,
+            cmd_big_cheer,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "BITS",
+            "Show the Users who have cheered the most Bits in a Channel.\n\n\
+                Usage: BITS [<channel>]",
+            Priority::NORMAL,
+            cmd_bits,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "BITSKEEP",
+            "Toggle whether the Bits cheered are kept in hextwitch/bits.log, \
+                inside the HexChat config directory, and counted again in \
+                later sessions. When the file grows long, it is trimmed to \
+                the highest totals.",
+            Priority::NORMAL,
+            cmd_bits_keep,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "CHATTERS",
+            "Add every User in the chat of the current Channel to the User \
+                List, through the Twitch API. This needs a Token of a \
+                Moderator, with the moderator:read:chatters scope.",
+            Priority::NORMAL,
+            cmd_chatters,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "CHEERMOTES",
+            "Set how Cheermotes, like \"Cheer100\", are shown in Messages: \
+                plain (unchanged), compact (replaced by one marker of their \
+                total Bits), or tiers (colored by their number of Bits).\n\n\
+                Usage: CHEERMOTES [plain|compact|tiers]",
+            Priority::NORMAL,
+            cmd_cheermotes,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "DUPEBYPASS",
+            "Toggle whether a Message repeated within 30 seconds, which Twitch \
+                would reject, is changed slightly so that it gets through. \
+                This is set for each Channel, and is off by default. \"list\" \
+                shows the Channels where it is on.\n\n\
+                Usage: DUPEBYPASS [<channel>|list]",
+            Priority::NORMAL,
+            cmd_dupe_bypass,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "FIRSTALERT",
+            "Toggle whether the first Message of a chatter new to a Channel is \
+                announced on its own line. It is always marked in the Badges \
+                with \"✱\", as a returning chatter is with \"↻\".",
+            Priority::NORMAL,
+            cmd_first_alert,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "GIFTBOMB",
+            "Toggle whether the Gifts of a Gift Bomb are shown on their own \
+                lines, rather than listed in one summary once they stop \
+                arriving.",
+            Priority::NORMAL,
+            cmd_gifts,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "HELIX",
+            "Set the Client ID and Token used for the Twitch API, or the base \
+                URL of the API, for example to use a local stand-in. These \
+                are kept in hextwitch/helix.conf, inside the HexChat config \
+                directory; \"reload\" reads that file again. The Token needs \
+                the channel:manage:broadcast scope to set Titles. A value of \
+                \"-\" removes the setting. \"check\" shows the owner of the \
+                Token.\n\n\
+                Usage: HELIX check|reload | url|client|token [<value>|-]",
+            Priority::NORMAL,
+            cmd_helix,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "HILITE",
+            "Manage the Rules which highlight Messages in Twitch Channels. A \
+                Rule applies in one Channel, or in all of them with \"*\", \
+                and matches Messages by kind: text (a phrase, in any case), \
+                regex, author (a login name), id (a User ID), badge or \
+                nobadge (such as moderator), bits (at least a number), first \
+                (a first-time chatter), or links. A matching Message is \
+                highlighted, may set its own Tab color, and with \"copy\" is \
+                also shown in the (highlights) Tab. Rules are kept in \
+                hextwitch/highlights.conf.\n\n\
+                Usage: HILITE list | del <number> | \
+                add [copy] [color <0-3>] <channel|*> <kind> [<value>]",
+            Priority::NORMAL,
+            cmd_hilite,
+        )));
         hooks.push(Hook::CommandHook(register_command(
             "HTDEBUG",
             "Toggle whether unknown UserNotices should show the full plain IRC.",
             Priority::NORMAL,
             cmd_ht_debug,
         )));
+        hooks.push(Hook::CommandHook(register_command(
+            "IDLETIME",
+            "Set the number of Minutes after which a silent User is removed \
+                from the User List. Zero disables removal.\n\n\
+                Usage: IDLETIME [<minutes>]",
+            Priority::NORMAL,
+            cmd_idle,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "LAGMARK",
+            "Mark Messages which arrive more than a number of Seconds after \
+                they were sent. Zero disables marking.\n\n\
+                Usage: LAGMARK [<seconds>]",
+            Priority::NORMAL,
+            cmd_lag_mark,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "NOTICES",
+            "Reload the templates for UserNotices, such as Subscriptions. The \
+                built-in templates are overridden by lines of the form \
+                \"<msg-id> = <template>\" in hextwitch/notices.conf, inside \
+                the HexChat config directory.",
+            Priority::NORMAL,
+            cmd_notices,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "RAIDERS",
+            "List the Users who first chatted within a number of Minutes after \
+                the most recent Raid on the current Channel.\n\n\
+                Usage: RAIDERS [<minutes>]",
+            Priority::NORMAL,
+            cmd_raiders,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "RAIDGREET",
+            "Set a message to send to a Channel when it is Raided, if you are \
+                a Moderator there. Placeholders like {raider} and \
+                {msg-param-viewerCount} are filled as in notices.conf. A \
+                message of \"-\" disables greeting.\n\n\
+                Usage: RAIDGREET [<message>|-]",
+            Priority::NORMAL,
+            cmd_raid_greeting,
+        )));
         hooks.push(Hook::CommandHook(register_command(
             "REWARD",
             "Set the Name of a Custom Reward.\n\n\
@@ -88,18 +267,87 @@ impl Plugin for HexTwitch {
             Priority::NORMAL,
             cmd_reward,
         )));
+        hooks.push(Hook::CommandHook(register_command(
+            "STREAMPOLL",
+            "Set the number of Seconds between checks of whether joined \
+                Channels are live, through the Twitch API. The topic of each \
+                Tab shows the live status, game, title and viewers. Zero \
+                disables checking.\n\n\
+                Usage: STREAMPOLL [<seconds>]",
+            Priority::NORMAL,
+            cmd_stream_poll,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "TABCOLOR",
+            "Set the Tab Color (0-3) used for a Category of Twitch Events, \
+                optionally only in one Channel. A Color of \"-\" removes the \
+                setting. Categories are: chat, highlight, sub, cheer, bigcheer, \
+                raid, moderation, whisper, reward, stream.\n\n\
+                Usage: TABCOLOR [<category> [<color>|- [<channel>]]]",
+            Priority::NORMAL,
+            cmd_tabcolor,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "TIGNORE",
+            "Manage the Rules which hide Messages in Twitch Channels. A Rule \
+                applies in one Channel, or in all of them with \"*\", and \
+                matches Messages by kind: author (a login name), id (a User \
+                ID, or the login of a User seen this session), badge or \
+                nobadge (such as subscriber, never matching the broadcaster, \
+                moderators, or staff), first (a first-time chatter), regex, \
+                text, links, or bits. Hidden Messages are counted, and \
+                the most recent can be seen with \"shown\". Rules are kept \
+                in hextwitch/ignores.conf.\n\n\
+                Usage: TIGNORE list | shown [<count>] | del <number> | \
+                add <channel|*> <kind> [<value>]",
+            Priority::NORMAL,
+            cmd_tignore,
+        )));
         hooks.push(Hook::CommandHook(register_command(
             "TITLE",
-            "Set the Title of a Twitch Channel.",
+            "Set the Title of a Twitch Channel, through the Twitch API. The \
+                Client ID and Token must be set with HELIX first.\n\n\
+                Usage: TITLE <channel> <title>",
             Priority::NORMAL,
             cmd_title,
         )));
+        hooks.push(Hook::CommandHook(register_command(
+            "TWITCHGROUP",
+            "Save a group of Channels to be joined at once with TWITCHJOIN \
+                @<name>. A value of \"-\" deletes the group. With only a \
+                name, the group is shown; without any, all groups are \
+                listed.\n\n\
+                Usage: TWITCHGROUP [<name> [<channel>[,<channel>...]|-]]",
+            Priority::NORMAL,
+            cmd_tgroup,
+        )));
         hooks.push(Hook::CommandHook(register_command(
             "TWITCHJOIN",
-            "Join a Channel, but only on the Twitch Network.",
+            "Join Channels, but only on the Twitch Network. Channels may be \
+                separated by commas or spaces, and groups saved with \
+                TWITCHGROUP are named with \"@\". Channels already joined \
+                are skipped, and the rest are joined gradually, to stay within \
+                the limits of Twitch.\n\n\
+                Usage: TWITCHJOIN <channel>[,<channel>...]|@<group>",
             Priority::NORMAL,
             cmd_tjoin,
         )));
+        hooks.push(Hook::CommandHook(register_command(
+            "TWHOIS",
+            "Show what is known about a Twitch User: their Badges and Messages \
+                in each Channel this session, and any Timeouts, Bans, or \
+                deleted Messages. If the Twitch API is set up with HELIX, \
+                details of their Account are shown too.\n\n\
+                Usage: TWHOIS <user>",
+            Priority::NORMAL,
+            cmd_twhois,
+        )));
+        hooks.push(Hook::CommandHook(register_command(
+            "UNREAD",
+            "List the Twitch Tabs which have unread Events.",
+            Priority::NORMAL,
+            cmd_unread,
+        )));
         hooks.push(Hook::CommandHook(register_command(
             "W",
             "Open a Whisper with a Twitch User.\n\n\
@@ -109,13 +357,47 @@ impl Plugin for HexTwitch {
         )));
         hooks.push(Hook::CommandHook(register_command(
             "WHISPERHERE",
-            "Toggle whether Twitch Whispers should be duplicated in the current Tab.",
+            "Set where Twitch Whispers are shown besides their own Tabs. With \
+                no arguments, toggle whether they are duplicated in the current \
+                Tab. Routes are: tab (own Tab only), always (also the current \
+                Tab), unfocused (also the focused Tab, if it is not on Twitch), \
+                aggregate (also the \"(whispers)\" Tab), ignore (not shown at all, \
+                only logged).\n\n\
+                Usage: WHISPERHERE [list | <route> [<user>] | - <user>]",
             Priority::NORMAL,
             cmd_whisper_here,
         )));
+        hooks.push(Hook::CommandHook(register_command(
+            "WHISTORY",
+            "Show logged Whispers with a Twitch User. With an offset, skip \
+                back over that many of the most recent, to page through \
+                older Whispers.\n\n\
+                Usage: WHISTORY <username> [<count> [<offset>]]",
+            Priority::NORMAL,
+            cmd_whistory,
+        )));
 
-        //  Hook for User Joins.
+        //  Hooks for User Joins and Parts.
         hook_print!(hooks, PrintEvent::JOIN, cb_join);
+        hook_print!(hooks, PrintEvent::PART, cb_part);
+
+        //  Hooks for Joins and Parts of the User, and lost connections.
+        hook_print!(hooks, PrintEvent::YOU_JOIN, cb_self);
+        hook_print!(hooks, PrintEvent::YOU_PART, cb_self);
+        hook_print!(hooks, PrintEvent::YOU_PART_WITH_REASON, cb_self);
+        hook_print!(hooks, PrintEvent::YOU_KICKED, cb_self);
+        hook_print!(hooks, PrintEvent::DISCONNECTED, cb_self);
+
+        //  Hook for topics set by the Plugin.
+        hook_print!(hooks, PrintEvent::TOPIC_CHANGE, cb_topic);
+
+        //  Hook for text typed into a Tab.
+        hooks.push(Hook::CommandHook(register_command(
+            "",
+            "",
+            Priority::NORMAL,
+            cb_input,
+        )));
 
         //  Hooks for User Messages.
         hook_print!(hooks, PrintEvent::CHANNEL_MESSAGE, cb_print);
@@ -132,12 +414,51 @@ impl Plugin for HexTwitch {
             cb_server,
         )));
 
-        //  Hook Tab Focus events.
+        //  Hook Timers to add new Users and remove idle Users.
+        hooks.push(Hook::TimerHook(add_timer_task(
+            Duration::from_millis(500),
+            cb_joins,
+        )));
+        hooks.push(Hook::TimerHook(add_timer_task(
+            Duration::from_secs(60),
+            cb_idle,
+        )));
+
+        //  Hook Timer to send queued Joins.
+        hooks.push(Hook::TimerHook(add_timer_task(
+            Duration::from_millis(500),
+            cb_outgoing,
+        )));
+
+        //  Hook Timer to summarize Gift Bombs.
+        hooks.push(Hook::TimerHook(add_timer_task(
+            Duration::from_millis(500),
+            cb_gifts,
+        )));
+
+        //  Hook Timer to finish requests to the Twitch API.
+        hooks.push(Hook::TimerHook(add_timer_task(
+            Duration::from_millis(250),
+            cb_helix,
+        )));
+
+        //  Hook Timer to check whether Channels are live.
+        hooks.push(Hook::TimerHook(add_timer_task(
+            Duration::from_secs(10),
+            cb_streams,
+        )));
+
+        //  Hook Tab Focus and Close events.
         hooks.push(Hook::WindowHook(add_window_event_listener(
             WindowEvent::FOCUS_TAB,
             Priority::NORMAL,
             cb_focus,
         )));
+        hooks.push(Hook::WindowHook(add_window_event_listener(
+            WindowEvent::CLOSE_CONTEXT,
+            Priority::NORMAL,
+            cb_close,
+        )));
 
         //  Report loadedness.
         print_plain(&format!("{} {} loaded", Self::NAME, Self::VERSION));
@@ -153,6 +474,7 @@ impl Drop for HexTwitch {
                 Hook::CommandHook(handle) => { deregister_command(handle) }
                 Hook::PrintHook(handle) => { remove_print_event_listener(handle) }
                 Hook::ServerHook(handle) => { remove_raw_server_event_listener(handle) }
+                Hook::TimerHook(handle) => { remove_timer_task(handle) }
                 Hook::WindowHook(handle) => { remove_window_event_listener(handle) }
             }
         }